@@ -39,17 +39,33 @@ use super::traits::RendererBase;
 use super::renderer::Frequalizer;
 use super::renderer::EyeOfHAL;
 use super::renderer::StereoTree;
+use super::renderer::Tuner;
+use super::renderer::ChromaWheel;
+use super::renderer::Meter;
+use super::renderer::Goniometer;
 use super::user_interface::UI;
 use super::traits::UIEvent;
 
 // Audio in/out
 use super::audio::{Audio, AudioEvent};
+use super::audio::window::WindowFunction;
+use super::audio::frames::{FFT_SIZES, OVERLAPS};
+use super::audio::bands::BandLayout;
+use super::audio::decibel::{DecibelScale, DECIBEL_RANGES};
+use super::audio::smoothing::Ballistics;
+use super::audio::chroma::REFERENCE_TUNINGS;
+use super::audio::view::SpectrumView;
+use super::audio::routing::ChannelRouting;
 
 // The tuple-array for the renderer selection dropdown
-const AVAILABLE_RENDERERS: [(&str, usize); 3] = [
+const AVAILABLE_RENDERERS: [(&str, usize); 7] = [
   ("Frequalizer", 0),
   ("The Eye of HAL", 1),
-  ("StereoTree", 3)
+  ("StereoTree", 2),
+  ("Tuner", 3),
+  ("Chroma wheel", 4),
+  ("Loudness meter", 5),
+  ("Goniometer", 6)
 ];
 
 const WINDOW_TITLE: &str = "VisualizRS";
@@ -102,6 +118,9 @@ impl App<'static> {
     }
     instance.user_interface.set_available_renderers(rend);
 
+    // ... and the host the audio system listens to, which determines the devices
+    instance.user_interface.selected_host_changed(instance.audio_io.host());
+
     // Set up the communication channel between the user interface and the application
     let (tx, rx) = mpsc::channel();
     instance.ui_action_rx = Some(rx);
@@ -139,7 +158,16 @@ impl App<'static> {
   fn update(&mut self, args: &UpdateArgs) {
     let now = time::Instant::now();
     if now.duration_since(self.last_check) > time::Duration::new(1, 0) {
-      self.window.set_title(format!("{} ({} fps)", WINDOW_TITLE, self.frame_counter));
+      let stats = self.audio_io.stats();
+      self.window.set_title(format!(
+        "{} ({} fps, latency: {} ms, {} of {} buffers dropped, {} samples overrun)",
+        WINDOW_TITLE,
+        self.frame_counter,
+        (stats.latency * 1_000.0).round(),
+        stats.dropped,
+        stats.received,
+        stats.overruns
+      ));
       self.last_check = now;
       self.frame_counter = 0;
     }
@@ -157,6 +185,62 @@ impl App<'static> {
           UIEvent::RequestChangeAudioDevice(idx) => {
             self.audio_io.switch_device(idx);
           },
+          UIEvent::RequestChangeWindow(idx) => {
+            if let Some(window) = WindowFunction::all().get(idx) {
+              self.audio_io.set_window(*window);
+            }
+          },
+          UIEvent::RequestChangeFftSize(idx) => {
+            if let Some(size) = FFT_SIZES.get(idx) {
+              self.audio_io.set_fft_size(*size);
+            }
+          },
+          UIEvent::RequestChangeOverlap(idx) => {
+            if let Some(overlap) = OVERLAPS.get(idx) {
+              self.audio_io.set_overlap(*overlap);
+            }
+          },
+          UIEvent::RequestChangeZeroPadding(zero_padding) => {
+            self.audio_io.set_zero_padding(zero_padding);
+          },
+          UIEvent::RequestChangeBandLayout(idx) => {
+            if let Some(layout) = BandLayout::all().get(idx) {
+              self.audio_io.set_band_layout(*layout);
+            }
+          },
+          UIEvent::RequestChangeDecibelRange(idx) => {
+            if let Some((floor, ceiling)) = DECIBEL_RANGES.get(idx) {
+              self.audio_io.set_decibel_scale(DecibelScale::create(*floor, *ceiling));
+            }
+          },
+          UIEvent::RequestChangeSmoothing(idx) => {
+            if let Some(ballistics) = Ballistics::all().get(idx) {
+              self.audio_io.set_ballistics(*ballistics);
+            }
+          },
+          UIEvent::RequestChangeReferenceTuning(idx) => {
+            if let Some(reference_tuning) = REFERENCE_TUNINGS.get(idx) {
+              self.audio_io.set_reference_tuning(*reference_tuning);
+            }
+          },
+          UIEvent::RequestChangeView(idx) => {
+            if let Some(view) = SpectrumView::all().get(idx) {
+              self.audio_io.set_view(*view);
+            }
+          },
+          UIEvent::RequestChangeStreamConfig(config) => {
+            self.audio_io.set_stream_config(config);
+          },
+          UIEvent::RequestChangeHost(idx) => {
+            if let Some(host) = cpal::available_hosts().get(idx) {
+              self.audio_io.set_host(*host);
+            }
+          },
+          UIEvent::RequestChangeChannelRouting(idx) => {
+            if let Some(routing) = ChannelRouting::all(self.audio_io.channels()).get(idx) {
+              self.audio_io.set_channel_routing(*routing);
+            }
+          },
           // We shall hot-swap the renderer
           UIEvent::RequestChangeRenderer(id) => {
             match id {
@@ -168,6 +252,22 @@ impl App<'static> {
                 self.renderer = Box::new(StereoTree::create());
                 self.user_interface.selected_renderer_changed(2);
               },
+              3 => {
+                self.renderer = Box::new(Tuner::create());
+                self.user_interface.selected_renderer_changed(3);
+              },
+              4 => {
+                self.renderer = Box::new(ChromaWheel::create());
+                self.user_interface.selected_renderer_changed(4);
+              },
+              5 => {
+                self.renderer = Box::new(Meter::create());
+                self.user_interface.selected_renderer_changed(5);
+              },
+              6 => {
+                self.renderer = Box::new(Goniometer::create());
+                self.user_interface.selected_renderer_changed(6);
+              },
               _ => {
                 // If uncovered, take this
                 self.renderer = Box::new(Frequalizer::create());
@@ -189,9 +289,35 @@ impl App<'static> {
     match self.audio_action_rx.as_ref().unwrap().try_recv() {
       Ok(event) => {
         match event {
+          AudioEvent::HostChanged(host) => {
+            println!("A new audio host has been selected: {}!", host.name());
+            self.user_interface.selected_host_changed(host);
+          },
           AudioEvent::InputDeviceChanged(idx) => {
             println!("A new audio input device has been selected: {}!", idx);
             self.user_interface.selected_audio_device_changed(idx);
+          },
+          AudioEvent::Error(e) => {
+            // The audio system has already logged it
+            self.user_interface.audio_error_occurred(e.to_string());
+          },
+          AudioEvent::InputLost => {
+            self.user_interface.input_lost();
+          },
+          AudioEvent::DevicesChanged(devices) => {
+            self.user_interface.available_devices_changed(devices);
+          },
+          AudioEvent::DeviceLost(name, fallback) => {
+            println!("The audio input device {} has been disconnected!", name);
+            match fallback {
+              Some(idx) => self.user_interface.selected_audio_device_changed(idx),
+              None => self.user_interface.input_lost()
+            }
+            self.user_interface.audio_error_occurred(format!("{} has been disconnected", name));
+          },
+          AudioEvent::DeviceReconnected(idx) => {
+            println!("The audio input device is back: {}!", idx);
+            self.user_interface.selected_audio_device_changed(idx);
           }
         }
       },
@@ -239,8 +365,9 @@ impl App<'static> {
     }
   }
 
-  fn on_keypress (&mut self, _key: Key) {
-    //
+  fn on_keypress (&mut self, key: Key) {
+    self.renderer.on_keypress(key);
+    self.user_interface.on_keypress(key);
   }
 
   pub fn main_loop(&mut self) {
@@ -1,205 +1,699 @@
-/// Audio input/output handler
+// Audio input/output handler
 
-use cpal::traits::{StreamTrait};
 use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Instant;
 use mpsc::TryRecvError;
 
-// FFT imports
-use rustfft::FFT;
-use rustfft::algorithm::Radix4;
-use rustfft::num_complex::Complex;
-use rustfft::num_traits::Zero;
-
 pub mod util;
+pub mod source;
+pub mod file;
+pub mod raw;
+pub mod generator;
+pub mod window;
+pub mod frames;
+pub mod bands;
+pub mod decibel;
+pub mod smoothing;
+pub mod beat;
+pub mod pitch;
+pub mod chroma;
+pub mod mel;
+pub mod constant_q;
+pub mod view;
+pub mod loudness;
+pub mod stereo;
+pub mod features;
+pub mod routing;
+pub mod error;
+pub mod hotplug;
+pub mod analyzer;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+
+use util::{open_source, fetch_devices, initial_host, default_input_name, AudioDevice, DeviceSource};
+use cpal::{StreamConfig, HostId};
+use source::{AudioSource, SourceKind, NoSource};
+use window::WindowFunction;
+use bands::BandLayout;
+use decibel::DecibelScale;
+use smoothing::Ballistics;
+use pitch::Pitch;
+use chroma::DEFAULT_REFERENCE_TUNING;
+use view::SpectrumView;
+use loudness::Loudness;
+use stereo::Stereo;
+use features::SpectralFeatures;
+use routing::ChannelRouting;
+use error::AudioError;
+use hotplug::{DeviceWatcher, DeviceList};
+use analyzer::{AnalysisWorker, AnalyzerCommand};
+use stats::StreamStats;
 
-use util::{create_stream};
+// By default, we analyze 2,048 samples at a time, with 50 % overlap
+const DEFAULT_FFT_SIZE: usize = 2_048;
+const DEFAULT_OVERLAP: f32 = 0.5;
+const DEFAULT_BAND_LAYOUT: BandLayout = BandLayout::Octave(6);
+const DEFAULT_VIEW: SpectrumView = SpectrumView::Bands;
+// At most this many seconds of samples wait for the analysis, anything older is dropped
+const MAX_BACKLOG: f32 = 0.25;
+// Buffers that were captured more than this many seconds before we fetch them are late
+const LATE_THRESHOLD: f32 = 0.1;
+// How fast the measured latency follows the current one (0.0 to 1.0)
+const LATENCY_SMOOTHING: f32 = 0.1;
 
 pub enum AudioEvent {
-  InputDeviceChanged(usize) // Emitted with the new device index
+  InputDeviceChanged(usize), // Emitted with the new device index
+  HostChanged(HostId), // Emitted with the new host, before the device of the host it listens to
+  Error(AudioError), // Emitted whenever a source couldn't be opened or failed
+  InputLost, // Emitted after an error the source didn't survive, we're listening to nothing now
+  DevicesChanged(Vec<AudioDevice>), // Emitted with the new device list whenever devices have been plugged in or out
+  DeviceLost(String, Option<usize>), // Emitted with the name of the unplugged device and the index of the one we fell back to, if any
+  DeviceReconnected(usize) // Emitted with the device index once the unplugged device is back
 }
 
 #[derive(Clone)] // Derive the clone ability, because all fields of this struct are clonable
 pub struct AnalyzedAudio {
-  pub amplitude: [Vec<f32>; 2], // The original PCM amplitude buffer (sample size)
-  pub frequency: [Vec<f32>; 2], // The analyzed frequency amplitudes (half the sample size)
-  pub buffer_size: u32, // Buffer length
+  // All buffers and spectra hold one entry per channel. Use routed() to get
+  // the channels the user wants to see on the left and right side.
+  pub amplitude: Vec<Vec<f32>>, // The original PCM amplitude buffer (sample size)
+  pub frequency: Vec<Vec<f32>>, // The analyzed frequency amplitudes (half the sample size)
+  pub smoothed_frequency: Vec<Vec<f32>>, // The frequency amplitudes with attack and release applied
+  pub frequency_peaks: Vec<Vec<f32>>, // The held (and slowly falling) peaks of the smoothed frequency amplitudes
+  pub bands: Vec<Vec<f32>>, // The frequency amplitudes grouped into log-spaced bands
+  pub band_frequencies: Vec<f32>, // The center frequency of each band
+  pub band_layout: BandLayout,
+  pub mel: Vec<Vec<f32>>, // The frequency amplitudes passed through a mel filterbank (empty unless the mel view is selected)
+  pub mel_frequencies: Vec<f32>, // The center frequency of each mel band
+  pub constant_q: Vec<Vec<f32>>, // The amplitudes of the constant-Q transform, one per semitone (empty unless its view is selected)
+  pub constant_q_frequencies: Vec<f32>, // The center frequency of each constant-Q bin
+  pub view: SpectrumView, // Which of the representations above spectrum() returns
+  pub smoothed_spectrum: Vec<Vec<f32>>, // The selected spectrum with attack and release applied
+  pub spectrum_peaks: Vec<Vec<f32>>, // The held (and slowly falling) peaks of the smoothed spectrum
+  pub routing: ChannelRouting, // Which channels routed() returns
+  pub decibel_scale: DecibelScale, // Use this to map the magnitudes to the display
+  pub ballistics: Ballistics, // How the smoothed values and peaks move
+  pub beat: bool, // True if the tracker fired on this frame. Renderers that may skip frames should rather watch beat_count.
+  pub beat_count: u64, // How many beats have been tracked so far. Renderers compare it to the last one they've seen to catch every beat.
+  pub onset_strength: f32, // How much new sound the last frame contained, 0.0 to 1.0
+  pub bpm: f32, // The estimated tempo, 0.0 if unknown
+  pub beat_phase: f32, // The position within the current beat, 0.0 (on the beat) to 1.0
+  pub pitch: Option<Pitch>, // The fundamental frequency and note, if there is a clear one
+  pub chroma: [f32; 12], // The energy per pitch class (C to B) of both channels, the strongest one is 1.0
+  pub reference_tuning: f32, // The frequency of A4 the notes and pitch classes are derived from
+  pub loudness: Loudness, // Levels and loudness, measured on the stream rather than the frames
+  pub stereo: Stereo, // Correlation, balance and the mid/side signals of the routed amplitude buffers
+  pub features: SpectralFeatures, // Centroid, flux, flatness and more of all channels combined
+  pub buffer_size: u32, // Buffer length (= FFT size)
+  pub hop_size: u32, // How many samples the analysis advances between two frames
   pub sample_rate: u32, // The sample rate, e.g. 44,100 Hz
+  pub transform_size: u32, // The length of the FFT, which is larger than the buffer if zero-padded
   pub bin_frequency: f32, // The frequency of the bins, e.g. 43Hz for sampling 44.1kHz at 1,024 buffer size
-  pub channels: usize, // The amount of channels we're recording with (mono or stereo)
+  pub channels: usize, // The amount of channels we're recording with
+  pub window: WindowFunction, // The window function applied before the FFT
+  pub captured_at: Option<Instant> // When the newest samples of this frame were captured, None until the first frame
+}
+
+impl AnalyzedAudio {
+  /// An empty analysis, until the first frame is in
+  pub fn create (sample_rate: u32, channels: usize) -> Self {
+    Self {
+      amplitude: Vec::new(),
+      frequency: Vec::new(),
+      smoothed_frequency: Vec::new(),
+      frequency_peaks: Vec::new(),
+      bands: Vec::new(),
+      band_frequencies: Vec::new(),
+      band_layout: DEFAULT_BAND_LAYOUT,
+      mel: Vec::new(),
+      mel_frequencies: Vec::new(),
+      constant_q: Vec::new(),
+      constant_q_frequencies: Vec::new(),
+      view: DEFAULT_VIEW,
+      smoothed_spectrum: Vec::new(),
+      spectrum_peaks: Vec::new(),
+      routing: ChannelRouting::initial(channels),
+      decibel_scale: DecibelScale::default(),
+      ballistics: Ballistics::default(),
+      beat: false,
+      beat_count: 0,
+      onset_strength: 0.0,
+      bpm: 0.0,
+      beat_phase: 0.0,
+      pitch: None,
+      chroma: [0.0; 12],
+      reference_tuning: DEFAULT_REFERENCE_TUNING,
+      loudness: Loudness::create(channels),
+      stereo: Stereo::create(),
+      features: SpectralFeatures::create(),
+      sample_rate,
+      bin_frequency: 0.0,
+      channels,
+      buffer_size: 0,
+      hop_size: 0,
+      transform_size: 0,
+      window: WindowFunction::Hann,
+      captured_at: None
+    }
+  }
+
+  /// Returns the spectral representation of the selected view
+  pub fn spectrum (&self) -> &[Vec<f32>] {
+    match self.view {
+      SpectrumView::Linear => &self.frequency,
+      SpectrumView::Bands => &self.bands,
+      SpectrumView::Mel(_) => &self.mel,
+      SpectrumView::ConstantQ => &self.constant_q
+    }
+  }
+
+  /// Returns the (center) frequency of each value of spectrum()
+  pub fn spectrum_frequencies (&self) -> Vec<f32> {
+    match self.view {
+      SpectrumView::Linear => {
+        let bin_count = self.frequency.first().map_or(0, |spectrum| spectrum.len());
+        (0..bin_count).map(|idx| idx as f32 * self.bin_frequency).collect()
+      },
+      SpectrumView::Bands => self.band_frequencies.clone(),
+      SpectrumView::Mel(_) => self.mel_frequencies.clone(),
+      SpectrumView::ConstantQ => self.constant_q_frequencies.clone()
+    }
+  }
+
+  /// Returns the left and right side of per-channel data according to the
+  /// routing, e.g. audio.routed(&audio.smoothed_spectrum)
+  pub fn routed (&self, channels: &[Vec<f32>]) -> [Vec<f32>; 2] {
+    self.routing.route(channels)
+  }
 }
 
 // Example directory: https://github.com/RustAudio/cpal/blob/master/examples
 pub struct Audio {
-  // Holds the stream (necessary to retrieve samples)
-  stream: Option<Box<dyn StreamTrait>>,
-  // Last read buffer slice
-  last_buffer: AnalyzedAudio,
-  thread_recv: mpsc::Receiver<std::vec::Vec<f32>>,
+  // Holds the source we're listening to (a device, a file, ...)
+  source: Box<dyn AudioSource>,
+  // Runs the analysis on a separate thread, so it doesn't hold up the rendering
+  analysis: AnalysisWorker,
+  stats: StreamStats,
   event_sender: Option<mpsc::Sender<AudioEvent>>,
+  pending_events: Vec<AudioEvent>, // Events that happened before anyone was listening
+  host: HostId, // The audio host (e.g. ALSA or JACK) whose devices we list and open
+  device_index: usize, // The index of the source within fetch_devices
+  devices: Vec<AudioDevice>, // All sources as of the last device list, the UI shows the same one
+  device_watcher: Option<DeviceWatcher>, // None unless watch_devices has been called
+  stream_config: Option<StreamConfig>, // The configuration picked for the device, reused when reconnecting
+  lost_device: Option<String>, // The input device that has been unplugged, we switch back once it reappears
+  input_lost: bool, // True while listening to nothing, we take the first device that appears
   // Necessary info for the current stream
   sample_rate: u32,
-  buffer_size: usize,
   channels: usize, // Holds the channel number
-  // bpm: usize // TODO: Actually calculate the bpm at some point
+  routing: ChannelRouting
 }
 
 impl Audio {
   pub fn create () -> Self {
-    // The input stream will live in a different thread, so we need a transmitter
-    // to safely transmit data to this (main) thread
-    let (stream, config, rx, _real_idx) = create_stream(Some(0), None); // By default, use the first device (not default, b/c we can't extract the device index)
-
-    let audio_buf = AnalyzedAudio {
-      amplitude: [Vec::new(), Vec::new()],
-      frequency: [Vec::new(), Vec::new()],
-      sample_rate: config.sample_rate.0, // Pry the sample rate out of this struct
-      bin_frequency: 0.0,
-      channels: config.channels as usize,
-      buffer_size: 0
+    let host = initial_host();
+    // By default, use the first device (not default, b/c we can't extract the device index)
+    let mut audio = match DeviceSource::open(host, Some(0), None) {
+      Ok((source, real_idx)) => {
+        let mut audio = Self::with_source(host, Box::new(source));
+        audio.device_index = real_idx;
+        audio
+      },
+      Err(e) => {
+        // Keep running without input, the user may still pick another source
+        let mut audio = Self::with_source(host, Box::new(NoSource));
+        audio.input_lost = true;
+        audio.send_event(AudioEvent::Error(e));
+        audio.send_event(AudioEvent::InputLost);
+        audio
+      }
     };
+    audio.watch_devices();
+    audio
+  } // END constructor
 
+  /// Creates the audio handler listening to an arbitrary source. It doesn't
+  /// touch the devices of the host until asked to, so it runs without any
+  /// audio system, e.g. in tests (see also watch_devices).
+  pub fn with_source (host: HostId, source: Box<dyn AudioSource>) -> Self {
     Self {
-      stream: Some(Box::new(stream)),
-      last_buffer: audio_buf, // Initialize with empty buffer
-      thread_recv: rx, // Save the receiver
+      analysis: AnalysisWorker::create(source.sample_rate(), source.channels()),
+      stats: StreamStats::create(),
       event_sender: None, // Used by the application to receive audio events
-      buffer_size: 0, // Will be set after the first sample set has been received b/c the default buffer size can be difficult
-      sample_rate: config.sample_rate.0,
-      // bpm: 0,
-      channels: config.channels as usize
+      pending_events: Vec::new(),
+      host,
+      device_index: 0,
+      devices: Vec::new(),
+      device_watcher: None,
+      stream_config: None,
+      lost_device: None,
+      input_lost: false,
+      routing: ChannelRouting::initial(source.channels()),
+      sample_rate: source.sample_rate(),
+      channels: source.channels(),
+      source
     }
-  } // END constructor
+  }
+
+  /// Starts watching the devices of the host, so we notice them being
+  /// plugged in and out. The device list is sent with DevicesChanged.
+  pub fn watch_devices (&mut self) {
+    self.device_watcher = Some(DeviceWatcher::create(self.host));
+  }
 
-  /// Switches to a different device.
+  /// Switches to a different device (or any other source, see fetch_devices).
   pub fn switch_device (&mut self, device_index: usize) {
-    let (stream, config, rx, real_index) = create_stream(Some(device_index), None);
-    self.sample_rate = config.sample_rate.0;
-    self.stream = Some(Box::new(stream));
-    self.thread_recv = rx;
-    if self.event_sender.is_some() {
-      self.event_sender.as_ref().unwrap().send(AudioEvent::InputDeviceChanged(real_index)).unwrap();
+    self.ensure_devices();
+    let opened = match self.devices.get(device_index) {
+      Some(device) => open_source(self.host, device, None),
+      None => Err(AudioError::Source(format!("There is no source {}", device_index + 1)))
+    };
+
+    match opened {
+      Ok(source) => {
+        self.device_index = device_index;
+        self.stream_config = None;
+        // The user's choice beats waiting for a lost device
+        self.lost_device = None;
+        self.input_lost = false;
+        self.set_source(source);
+        self.send_event(AudioEvent::InputDeviceChanged(device_index));
+      },
+      Err(e) => {
+        // Keep listening to whatever we had before
+        self.send_event(AudioEvent::Error(e));
+      }
     }
   }
 
-  /// Registers an event transmitter to receive feedback on some changes in the audio system
-  pub fn register_action_callback (&mut self, tx: mpsc::Sender<AudioEvent>) {
-    self.event_sender = Some(tx);
+  /// Switches to another audio host (e.g. from ALSA to JACK) and listens to
+  /// its first device. If the host is unavailable, we stay where we are.
+  pub fn set_host (&mut self, host: HostId) {
+    let devices = match fetch_devices(host) {
+      Ok(devices) => devices,
+      Err(e) => {
+        self.send_event(AudioEvent::Error(e));
+        return;
+      }
+    };
+
+    println!("Switching to host {} ...", host.name());
+    self.host = host;
+    if self.device_watcher.is_some() {
+      self.device_watcher = Some(DeviceWatcher::create(host));
+    }
+    self.devices = devices.clone();
+    // Neither the lost device nor the configuration carry over to the new host
+    self.lost_device = None;
+    self.stream_config = None;
+    self.send_event(AudioEvent::HostChanged(host));
+    self.send_event(AudioEvent::DevicesChanged(devices));
+
+    let first_input = self.devices.iter().position(|device| matches!(device.kind, SourceKind::Input));
+    match first_input.ok_or(AudioError::NoDevice).and_then(|index| self.connect_device(index, None)) {
+      Ok(()) => self.send_event(AudioEvent::InputDeviceChanged(self.device_index)),
+      Err(e) => {
+        self.send_event(AudioEvent::Error(e));
+        self.set_source(Box::new(NoSource));
+        self.input_lost = true;
+        self.send_event(AudioEvent::InputLost);
+      }
+    }
   }
 
-  fn analyze (&mut self, buf: Vec<f32>) {
-    // Why don't we take this from the config? B/c the default buffer size is
-    // undefined, but we'll know it as soon as we have the sample
-    // NOTE: This buffer is INTERLEAVED, that means LRLRLRLRLRLR ...!!!
-    self.buffer_size = buf.len() / self.channels; // len for mono, len/2 for stereo
+  /// Returns the audio host whose devices we list and open
+  pub fn host (&self) -> HostId {
+    self.host
+  }
 
-    let mut buffers: [Vec<f32>; 2] = [
-      Vec::new(),
-      Vec::new()
-    ]; // If it's mono, we'll duplicate the full buffer
+  /// Reopens the current input device with a different stream configuration,
+  /// e.g. one returned by AudioDevice::closest_config
+  pub fn set_stream_config (&mut self, config: StreamConfig) {
+    let name = match self.source.device_name() {
+      Some(name) => name,
+      None => return // All other sources determine their format themselves
+    };
 
-    if self.channels == 2 {
-      // This basically omits all channels over 2
-      for i in 0..self.buffer_size {
-        buffers[0].push(buf[i * self.channels]);
-        buffers[1].push(buf[i * self.channels + 1]);
+    match DeviceSource::open_named(self.host, &name, Some(config.clone())) {
+      Ok(source) => {
+        self.stream_config = Some(config);
+        self.set_source(Box::new(source));
+      },
+      Err(e) => self.send_event(AudioEvent::Error(e))
+    }
+  }
+
+  /// Reacts to a source that failed for good. An unplugged input device is
+  /// replaced by the next best one until it's back, all other sources by
+  /// listening to nothing.
+  fn lose_input (&mut self, error: AudioError) {
+    match self.source.device_name() {
+      Some(name) => {
+        println!("ERROR: {}", error);
+        self.lost_device = Some(name.clone());
+        let fallback = self.open_fallback(&name);
+        self.send_event(AudioEvent::DeviceLost(name, fallback));
+      },
+      None => {
+        self.send_event(AudioEvent::Error(error));
+        self.set_source(Box::new(NoSource));
+        self.input_lost = true;
+        self.send_event(AudioEvent::InputLost);
       }
-    } else if self.channels == 1 {
-      for sample in buf.iter() {
-        buffers[0].push(*sample);
-        buffers[1].push(*sample);
+    }
+  }
+
+  /// Opens the default input device, or the first other one if that's the
+  /// lost one as well, and returns its index. If there is none, we listen to
+  /// nothing and return None.
+  fn open_fallback (&mut self, lost_device: &str) -> Option<usize> {
+    self.ensure_devices();
+    let default_device = default_input_name(self.host);
+    let mut candidates: Vec<&AudioDevice> = self.devices.iter()
+      .filter(|device| matches!(device.kind, SourceKind::Input) && device.name != lost_device)
+      .collect();
+    // Stable, so the others keep their order
+    candidates.sort_by_key(|device| Some(&device.name) != default_device.as_ref());
+    let candidates: Vec<usize> = candidates.into_iter().map(|device| device.index).collect();
+
+    for index in candidates {
+      match self.connect_device(index, None) {
+        Ok(()) => return Some(index),
+        Err(e) => println!("ERROR: {}", e) // Try the next one
       }
     }
 
-    // Perform FFT on both buffers. For computational reason,
-    // first on the first, and if we have mono data, also just
-    // clone it, instead of running the FFT again.
-    let mut output: [Vec<f32>; 2] = [
-      Vec::new(),
-      Vec::new()
-    ];
-
-    output[0] = self.run_fft(&buffers[0]);
-    if self.channels == 2 {
-      // Run the FFT on the right channel as well
-      output[1] = self.run_fft(&buffers[1]);
-    } else {
-      // Copy the left channel
-      output[1] = output[0].clone();
-    }
-
-    self.last_buffer = AnalyzedAudio {
-      amplitude: buffers,
-      frequency: output,
-      sample_rate: self.sample_rate,
-      bin_frequency: self.sample_rate as f32 / self.buffer_size as f32,
-      channels: self.channels,
-      buffer_size: self.buffer_size as u32
+    self.set_source(Box::new(NoSource));
+    self.input_lost = true;
+    None
+  }
+
+  /// Listens to the input device at the index of the device list. If it
+  /// doesn't support the configuration, it's opened with its default one.
+  fn connect_device (&mut self, device_index: usize, config: Option<StreamConfig>) -> Result<(), AudioError> {
+    let name = self.devices.get(device_index).ok_or(AudioError::NoDevice)?.name.clone();
+    let source = match DeviceSource::open_named(self.host, &name, config.clone()) {
+      Err(_) if config.is_some() => DeviceSource::open_named(self.host, &name, None)?,
+      result => result?
     };
+    self.device_index = device_index;
+    self.input_lost = false;
+    self.set_source(Box::new(source));
+    Ok(())
   }
 
-  /// Runs an FFT run on an audio buffer for one channel.
-  fn run_fft (&mut self, buf: &[f32]) -> Vec<f32> {
-    let mut input: Vec<Complex<f32>> = Vec::new();
-    for sample in buf.iter() {
-      input.push(Complex::from(*sample));
+  /// Fetches the device list ourselves if the watcher hasn't sent one yet
+  fn ensure_devices (&mut self) {
+    if self.devices.is_empty() {
+      self.devices = fetch_devices(self.host).unwrap_or_else(|e| {
+        println!("ERROR: {}", e);
+        Vec::new()
+      });
     }
+  }
+
+  /// Takes in a new device list from the watcher. The indices shift when
+  /// devices come and go, so we look our source up by name. If our device is
+  /// gone, it's lost just like after a stream error. If the lost device is
+  /// back, we switch back to it, and if we're listening to nothing, we take
+  /// the first device there is.
+  fn devices_changed (&mut self, list: DeviceList) {
+    let DeviceList { mut devices, present } = list;
+    let device_name = self.source.device_name();
+    let current = device_name.clone().or_else(|| self.devices.get(self.device_index).map(|device| device.name.clone()));
 
-    // We also need the reference value (e.g. the highest amplitude)
-    let mut max_amplitude: f32 = 0.0;
-    for sample in buf.iter() {
-      if sample.abs() > max_amplitude {
-        max_amplitude = *sample;
+    // The device we listen to may be too busy to be listed, although it's
+    // still there. It keeps its old entry then, so the UI can still show it.
+    if let Some(name) = device_name.as_ref().filter(|name| present.contains(name)) {
+      let listed = devices.iter().any(|device| &device.name == name);
+      match self.devices.iter().find(|device| &device.name == name) {
+        Some(entry) if !listed => {
+          let input_count = devices.iter().filter(|device| matches!(device.kind, SourceKind::Input)).count();
+          devices.insert(input_count, entry.clone());
+          for (index, device) in devices.iter_mut().enumerate() {
+            device.index = index;
+          }
+        },
+        _ => { /* Listed, or we never knew its entry */ }
       }
     }
 
-    // output must be equal to input, so we'll just prepopulate with zeroes
-    let mut output: Vec<Complex<f32>> = vec![Complex::zero(); input.len()];
+    if let Some(index) = current.and_then(|name| devices.iter().position(|device| device.name == name)) {
+      self.device_index = index;
+    }
+    self.devices = devices.clone();
+    // The UI needs the new list before we pick a device from it
+    self.send_event(AudioEvent::DevicesChanged(devices));
 
-    // We use Radix4, as audio buffers are always a power of 2. And if not
-    // we know the drill: Pad with zeroes!
-    let fft = Radix4::new(input.len(), false);
-    fft.process(&mut input, &mut output); // Aaaaaand GO!
+    // Our device has been unplugged, even if its stream hasn't noticed
+    if let Some(name) = device_name.filter(|name| !present.contains(name)) {
+      self.lose_input(AudioError::Device(format!("{} has been unplugged", name)));
+      return;
+    }
 
-    let mut output: Vec<f32> = output.iter().map(| el | {
-      // So, what we have in the output currently is a set of complex numbers which represent both the phase (which we
-      // don't need) and the magnitude/amplitude at the given frequency (which we want). There are multiple solutions, apparently,
-      // but what we actually want is sqrt(a^2 + b^2), so let's do that. Luckily, the complex number provides us with exactly that function:
-      el.norm_sqr().sqrt() // Important: We have to call sqrt later on ourselves!
-    }).collect();
+    let inputs: Vec<(usize, String)> = self.devices.iter()
+      .filter(|device| matches!(device.kind, SourceKind::Input))
+      .map(|device| (device.index, device.name.clone()))
+      .collect();
 
-    output.drain(0..output.len() / 2 - 1); // now it should be buf.len / 4
+    let lost_device = self.lost_device.clone();
+    if let Some((index, name)) = inputs.iter().find(|(_, name)| Some(name) == lost_device.as_ref()) {
+      match self.connect_device(*index, self.stream_config.clone()) {
+        Ok(()) => {
+          println!("Reconnected to device {}", name);
+          self.lost_device = None;
+          self.send_event(AudioEvent::DeviceReconnected(*index));
+        },
+        // We'll try again when the devices change the next time
+        Err(e) => println!("ERROR: {}", e)
+      }
+    } else if self.input_lost {
+      if let Some((index, _)) = inputs.first() {
+        match self.connect_device(*index, None) {
+          Ok(()) => self.send_event(AudioEvent::InputDeviceChanged(*index)),
+          Err(e) => println!("ERROR: {}", e)
+        }
+      }
+    }
+  }
 
-    // Cut off the 22,050Hz frequency (in case of 44.1kHz and 1,024 buffer size)
-    // as well as the DC point
-    output[0] = 0.0;
-    let last_idx = output.len() - 1;
-    output[last_idx] = 0.0;
+  /// Hot-swaps the source we're listening to
+  pub fn set_source (&mut self, source: Box<dyn AudioSource>) {
+    if let Err(e) = self.source.pause() {
+      // It's gone anyway
+      println!("ERROR: {}", e);
+    }
+    println!("Switching to source {} ...", source.name());
+    self.sample_rate = source.sample_rate();
+    self.channels = source.channels();
+    // Keep the routing if the new source has the same channels
+    if !ChannelRouting::all(self.channels).contains(&self.routing) {
+      self.routing = ChannelRouting::initial(self.channels);
+    }
+    self.analysis.send(AnalyzerCommand::SetSource(self.sample_rate, self.channels, self.routing));
+    self.source = source;
+    if let Err(e) = self.source.play() {
+      self.send_event(AudioEvent::Error(e));
+    }
+  }
+
+  /// Sets the amount of samples per FFT frame. This determines the frequency
+  /// resolution (sample rate / FFT size), but also the latency.
+  pub fn set_fft_size (&mut self, fft_size: usize) {
+    self.analysis.send(AnalyzerCommand::SetFftSize(fft_size));
+  }
+
+  /// Enables or disables padding the frames with zeroes to the next power of
+  /// two. Without it, the FFT runs on the exact frame size.
+  pub fn set_zero_padding (&mut self, zero_padding: bool) {
+    self.analysis.send(AnalyzerCommand::SetZeroPadding(zero_padding));
+  }
+
+  /// Sets how the spectrum is grouped into bands
+  pub fn set_band_layout (&mut self, band_layout: BandLayout) {
+    self.analysis.send(AnalyzerCommand::SetBandLayout(band_layout));
+  }
+
+  /// Sets which spectral representation the renderers display
+  pub fn set_view (&mut self, view: SpectrumView) {
+    self.analysis.send(AnalyzerCommand::SetView(view));
+  }
+
+  /// Sets which channels the renderers display
+  pub fn set_channel_routing (&mut self, routing: ChannelRouting) {
+    self.routing = routing;
+    self.analysis.send(AnalyzerCommand::SetChannelRouting(routing));
+  }
+
+  /// Returns the amount of channels of the current source
+  pub fn channels (&self) -> usize {
+    self.channels
+  }
+
+  /// Sets the range of the dBFS scale the renderers display
+  pub fn set_decibel_scale (&mut self, decibel_scale: DecibelScale) {
+    self.analysis.send(AnalyzerCommand::SetDecibelScale(decibel_scale));
+  }
+
+  /// Sets how fast the smoothed spectrum follows the raw one, and how the
+  /// peaks behave. This also applies to the selected view.
+  pub fn set_ballistics (&mut self, ballistics: Ballistics) {
+    self.analysis.send(AnalyzerCommand::SetBallistics(ballistics));
+  }
+
+  /// Sets the frequency of A4 the notes and pitch classes are derived from
+  pub fn set_reference_tuning (&mut self, reference_tuning: f32) {
+    self.analysis.send(AnalyzerCommand::SetReferenceTuning(reference_tuning));
+  }
+
+  /// Sets the overlap of consecutive frames, e.g. 0.75 to run an FFT every
+  /// quarter frame. More overlap means more frequent updates.
+  pub fn set_overlap (&mut self, overlap: f32) {
+    self.analysis.send(AnalyzerCommand::SetOverlap(overlap));
+  }
+
+  /// Sets the window function to apply before the FFT
+  pub fn set_window (&mut self, window: WindowFunction) {
+    self.analysis.send(AnalyzerCommand::SetWindow(window));
+  }
+
+  /// Registers an event transmitter to receive feedback on some changes in the audio system
+  pub fn register_action_callback (&mut self, tx: mpsc::Sender<AudioEvent>) {
+    self.event_sender = Some(tx);
+    for event in std::mem::take(&mut self.pending_events) {
+      self.send_event(event);
+    }
+  }
+
+  /// Sends an event to the application, or keeps it until it's listening
+  fn send_event (&mut self, event: AudioEvent) {
+    if let AudioEvent::Error(e) = &event {
+      println!("ERROR: {}", e);
+    }
 
-    // And return
-    output
+    match self.event_sender.as_ref() {
+      // If the application has hung up, nobody cares anymore
+      Some(sender) => { let _ = sender.send(event); },
+      None => self.pending_events.push(event)
+    }
   }
 
   pub fn fetch_new_audio (&mut self) {
-    match self.thread_recv.try_recv() {
-      Ok(buf) => self.analyze(buf),
-      Err(TryRecvError::Empty) => { /* All good, no buffer data available, continue as we were */ },
-      Err(TryRecvError::Disconnected) => {
-        // TODO: Reconnect to stream if possible
-        println!("The remote thread has terminated!");
+    if let Some(list) = self.device_watcher.as_ref().and_then(|watcher| watcher.poll()) {
+      self.devices_changed(list);
+    }
+
+    match self.source.error() {
+      // Without the device, there's nothing left to listen to
+      Some(e @ AudioError::Device(_)) => {
+        self.lose_input(e);
+        return;
+      },
+      Some(e) => self.send_event(AudioEvent::Error(e)),
+      None => { /* All good */ }
+    }
+
+    // Take everything the source has queued up since the last update
+    let mut buffers = Vec::new();
+    loop {
+      match self.source.try_recv() {
+        Ok(buf) => buffers.push(buf),
+        Err(TryRecvError::Empty) => break, // All caught up
+        Err(TryRecvError::Disconnected) => {
+          self.lose_input(AudioError::Disconnected);
+          return;
+        }
       }
     }
+
+    self.stats.received += buffers.len() as u64;
+    self.stats.overruns += self.source.take_overruns() as u64;
+
+    // If the analysis falls behind, only the freshest samples are worth
+    // analyzing. So we keep the newest buffers that fit into the backlog,
+    // together with what the analysis hasn't gotten to yet. If it's idle,
+    // the newest buffer is always taken, no matter how large.
+    let max_backlog = (self.sample_rate as f32 * MAX_BACKLOG) as usize * self.channels;
+    let mut backlog = self.analysis.pending_samples();
+    let mut first_kept = buffers.len();
+    while first_kept > 0 && (backlog == 0 || backlog + buffers[first_kept - 1].0.len() <= max_backlog) {
+      first_kept -= 1;
+      backlog += buffers[first_kept].0.len();
+    }
+    self.stats.dropped += first_kept as u64;
+
+    // Several buffers queue up between two updates anyway, so only the ones
+    // we dropped or that have waited too long are late
+    let late_kept = buffers[first_kept..].iter()
+      .filter(|(_, captured_at)| captured_at.elapsed().as_secs_f32() > LATE_THRESHOLD)
+      .count();
+    self.stats.late += (first_kept + late_kept) as u64;
+
+    for (buf, captured_at) in buffers.drain(first_kept..) {
+      self.analysis.send(AnalyzerCommand::Samples(buf, captured_at));
+    }
+  }
+
+  /// Returns the buffer statistics of the stream and the latency
+  pub fn stats (&self) -> StreamStats {
+    self.stats
   }
 
-  pub fn get_analyzed_audio (&mut self) -> AnalyzedAudio {
-    self.last_buffer.clone() // Return a clone of the buffer
+  /// Returns the newest analyzed frame, which is shared rather than copied
+  pub fn get_analyzed_audio (&mut self) -> Arc<AnalyzedAudio> {
+    let analyzed = self.analysis.latest();
+    if let Some(captured_at) = analyzed.captured_at {
+      let latency = captured_at.elapsed().as_secs_f32();
+      self.stats.latency += (latency - self.stats.latency) * LATENCY_SMOOTHING;
+    }
+    analyzed
   }
 }
 
 impl Drop for Audio {
   fn drop (&mut self) {
     println!("Audio shutting down ...");
-    if self.stream.is_some() {
-      self.stream.as_ref().unwrap().pause().expect("Could not stop stream!");
+    if let Err(e) = self.source.pause() {
+      println!("ERROR: {}", e);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+  use source::ChannelSource;
+  use generator::Waveform;
+  use testing::stereo;
+
+  #[test]
+  fn analyzes_a_source_without_any_audio_system () {
+    let (tx, rx) = mpsc::channel();
+    let source = ChannelSource::create(String::from("Test"), 44_100, 2, rx);
+    let mut audio = Audio::with_source(cpal::default_host().id(), Box::new(source));
+
+    for chunk in stereo(Waveform::Sine, 1_000.0, 0.5, 44_100, 4_096).chunks(2_048) {
+      tx.send((chunk.to_vec(), Instant::now())).unwrap();
     }
+    audio.fetch_new_audio();
+
+    // The analysis runs on its own thread, so give it a moment
+    let mut analyzed = audio.get_analyzed_audio();
+    for _ in 0..100 {
+      if analyzed.captured_at.is_some() {
+        break;
+      }
+      std::thread::sleep(Duration::from_millis(10));
+      analyzed = audio.get_analyzed_audio();
+    }
+
+    assert!(analyzed.captured_at.is_some(), "No frame has been analyzed");
+    assert_eq!(analyzed.channels, 2);
+    assert_eq!(audio.stats().received, 4);
+    let spectrum = &analyzed.frequency[0];
+    let peak = (0..spectrum.len()).max_by(|a, b| spectrum[*a].partial_cmp(&spectrum[*b]).unwrap()).unwrap();
+    assert!((peak as f32 * analyzed.bin_frequency - 1_000.0).abs() < analyzed.bin_frequency);
   }
 }
@@ -0,0 +1,500 @@
+// Analyzer -- turns the incoming samples into AnalyzedAudio on a thread of its own
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use std::thread;
+use arc_swap::ArcSwap;
+
+// FFT imports
+use rustfft::FFTplanner;
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+use super::{AnalyzedAudio, DEFAULT_FFT_SIZE, DEFAULT_OVERLAP, DEFAULT_BAND_LAYOUT, DEFAULT_VIEW};
+use super::window::WindowFunction;
+use super::frames::FrameBuffer;
+use super::bands::{BandLayout, Band, group_bins};
+use super::decibel::DecibelScale;
+use super::smoothing::{Ballistics, Smoother};
+use super::beat::BeatTracker;
+use super::pitch::detect_pitch;
+use super::chroma::{fold_chroma, DEFAULT_REFERENCE_TUNING};
+use super::mel::{MelFilterbank, DEFAULT_MEL_BAND_COUNT};
+use super::constant_q::ConstantQ;
+use super::view::SpectrumView;
+use super::loudness::LoudnessMeter;
+use super::stereo::analyze_stereo;
+use super::features::FeatureExtractor;
+use super::routing::{ChannelRouting, downmix};
+
+/// Everything the audio handler tells the analysis thread
+pub enum AnalyzerCommand {
+  Samples(Vec<f32>, Instant), // A buffer of interleaved samples of the current source, and when it was captured
+  SetSource(u32, usize, ChannelRouting), // The sample rate, channels and routing of a new source
+  SetFftSize(usize),
+  SetOverlap(f32),
+  SetZeroPadding(bool),
+  SetWindow(WindowFunction),
+  SetBandLayout(BandLayout),
+  SetView(SpectrumView),
+  SetChannelRouting(ChannelRouting),
+  SetDecibelScale(DecibelScale),
+  SetBallistics(Ballistics),
+  SetReferenceTuning(f32)
+}
+
+/// The handle of the analysis thread. The thread analyzes whatever samples
+/// it receives and publishes each analyzed frame, which the renderers can
+/// pick up at any time without waiting for the analysis. It winds down as
+/// soon as the handle is dropped.
+pub struct AnalysisWorker {
+  sender: mpsc::Sender<AnalyzerCommand>,
+  latest: Arc<ArcSwap<AnalyzedAudio>>, // The newest analyzed frame
+  pending: Arc<AtomicUsize> // How many samples have been sent, but not analyzed yet
+}
+
+impl AnalysisWorker {
+  pub fn create (sample_rate: u32, channels: usize) -> Self {
+    let (tx, rx) = mpsc::channel();
+    let latest = Arc::new(ArcSwap::from_pointee(AnalyzedAudio::create(sample_rate, channels)));
+    let published = latest.clone();
+    let pending = Arc::new(AtomicUsize::new(0));
+    let analyzed_samples = pending.clone();
+
+    thread::spawn(move || {
+      let mut analyzer = Analyzer::create(sample_rate, channels);
+      // Runs until the handle (and with it the sender) is gone
+      for command in rx.iter() {
+        let samples = match &command {
+          AnalyzerCommand::Samples(buf, _) => buf.len(),
+          _ => 0
+        };
+        if let Some(analyzed) = analyzer.process(command) {
+          published.store(Arc::new(analyzed));
+        }
+        analyzed_samples.fetch_sub(samples, Ordering::SeqCst);
+      }
+    });
+
+    Self {
+      sender: tx,
+      latest,
+      pending
+    }
+  }
+
+  /// Hands a command to the analysis thread
+  pub fn send (&self, command: AnalyzerCommand) {
+    if let AnalyzerCommand::Samples(buf, _) = &command {
+      self.pending.fetch_add(buf.len(), Ordering::SeqCst);
+    }
+    if self.sender.send(command).is_err() {
+      println!("ERROR: The analysis thread has terminated!");
+    }
+  }
+
+  /// Returns the newest analyzed frame. This only clones a pointer.
+  pub fn latest (&self) -> Arc<AnalyzedAudio> {
+    self.latest.load_full()
+  }
+
+  /// How many samples are still waiting for the analysis
+  pub fn pending_samples (&self) -> usize {
+    self.pending.load(Ordering::SeqCst)
+  }
+}
+
+/// Holds the state of the analysis, which carries over from frame to frame
+struct Analyzer {
+  // Necessary info for the current stream
+  sample_rate: u32,
+  channels: usize,
+  // Collects the incoming samples until we have a full frame
+  frame_buffer: FrameBuffer,
+  fft_size: usize,
+  overlap: f32, // 0.0 (none) to < 1.0
+  zero_padding: bool, // If true, frames are padded to the next power of two
+  planner: FFTplanner<f32>, // Creates (and caches) the FFT algorithms for each length
+  band_layout: BandLayout,
+  bands: Vec<Band>, // Cached, b/c they only change with the layout
+  decibel_scale: DecibelScale,
+  ballistics: Ballistics,
+  // Keep the state of each bin and band across frames
+  frequency_smoothers: Vec<Smoother>, // One per channel
+  spectrum_smoothers: Vec<Smoother>, // These follow the selected view
+  routing: ChannelRouting,
+  view: SpectrumView,
+  mel_band_count: usize,
+  mel_filterbank: Option<MelFilterbank>, // Cached, b/c it only changes with the FFT length
+  constant_q: Option<ConstantQ>, // Cached, b/c it only changes with the frame size
+  window: WindowFunction,
+  window_coefficients: Vec<f32>, // Cached, b/c they only change with the buffer size or window
+  beat_tracker: BeatTracker,
+  beat_count: u64, // How many beats have been tracked so far
+  feature_extractor: FeatureExtractor,
+  reference_tuning: f32, // The frequency of A4 in Hz
+  loudness_meter: LoudnessMeter
+}
+
+impl Analyzer {
+  fn create (sample_rate: u32, channels: usize) -> Self {
+    Self {
+      sample_rate,
+      channels,
+      frame_buffer: FrameBuffer::create(DEFAULT_FFT_SIZE, hop_size(DEFAULT_FFT_SIZE, DEFAULT_OVERLAP)),
+      fft_size: DEFAULT_FFT_SIZE,
+      overlap: DEFAULT_OVERLAP,
+      zero_padding: false,
+      planner: FFTplanner::new(false),
+      band_layout: DEFAULT_BAND_LAYOUT,
+      bands: DEFAULT_BAND_LAYOUT.bands(),
+      decibel_scale: DecibelScale::default(),
+      ballistics: Ballistics::default(),
+      frequency_smoothers: Vec::new(),
+      spectrum_smoothers: Vec::new(),
+      routing: ChannelRouting::initial(channels),
+      view: DEFAULT_VIEW,
+      mel_band_count: DEFAULT_MEL_BAND_COUNT,
+      mel_filterbank: None,
+      constant_q: None,
+      window: WindowFunction::Hann,
+      window_coefficients: Vec::new(),
+      beat_tracker: BeatTracker::create(),
+      beat_count: 0,
+      feature_extractor: FeatureExtractor::create(),
+      reference_tuning: DEFAULT_REFERENCE_TUNING,
+      loudness_meter: LoudnessMeter::create(sample_rate, channels)
+    }
+  }
+
+  /// Carries out a command. Returns the last analyzed frame if it produced any.
+  fn process (&mut self, command: AnalyzerCommand) -> Option<AnalyzedAudio> {
+    match command {
+      AnalyzerCommand::Samples(buf, captured_at) => return self.analyze(buf, captured_at),
+      AnalyzerCommand::SetSource(sample_rate, channels, routing) => {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.routing = routing;
+        self.frame_buffer.clear(); // These samples belong to the old source
+        self.reset_smoothers();
+        self.beat_tracker.reset();
+        self.feature_extractor.reset();
+        self.loudness_meter = LoudnessMeter::create(sample_rate, channels);
+      },
+      AnalyzerCommand::SetFftSize(fft_size) => {
+        self.fft_size = fft_size;
+        self.frame_buffer = FrameBuffer::create(self.fft_size, hop_size(self.fft_size, self.overlap));
+      },
+      AnalyzerCommand::SetOverlap(overlap) => {
+        self.overlap = overlap;
+        self.frame_buffer = FrameBuffer::create(self.fft_size, hop_size(self.fft_size, self.overlap));
+      },
+      AnalyzerCommand::SetZeroPadding(zero_padding) => self.zero_padding = zero_padding,
+      AnalyzerCommand::SetWindow(window) => {
+        self.window = window;
+        self.window_coefficients.clear(); // Will be recalculated on the next run
+      },
+      AnalyzerCommand::SetBandLayout(band_layout) => {
+        self.band_layout = band_layout;
+        self.bands = band_layout.bands();
+        for smoother in self.spectrum_smoothers.iter_mut() {
+          smoother.reset();
+        }
+      },
+      AnalyzerCommand::SetView(view) => {
+        if let SpectrumView::Mel(count) = view {
+          self.mel_band_count = count;
+        }
+        self.view = view;
+        for smoother in self.spectrum_smoothers.iter_mut() {
+          smoother.reset();
+        }
+      },
+      AnalyzerCommand::SetChannelRouting(routing) => self.routing = routing,
+      AnalyzerCommand::SetDecibelScale(decibel_scale) => self.decibel_scale = decibel_scale,
+      AnalyzerCommand::SetBallistics(ballistics) => self.ballistics = ballistics,
+      AnalyzerCommand::SetReferenceTuning(reference_tuning) => self.reference_tuning = reference_tuning
+    }
+    None
+  }
+
+  /// Returns the length of the FFT for a frame of the given size
+  fn transform_size (&self, frame_size: usize) -> usize {
+    if self.zero_padding {
+      frame_size.next_power_of_two()
+    } else {
+      frame_size
+    }
+  }
+
+  /// Forgets the smoothed values and peaks of all bins and bands
+  fn reset_smoothers (&mut self) {
+    for smoother in self.frequency_smoothers.iter_mut().chain(self.spectrum_smoothers.iter_mut()) {
+      smoother.reset();
+    }
+  }
+
+  fn analyze (&mut self, buf: Vec<f32>, captured_at: Instant) -> Option<AnalyzedAudio> {
+    // The loudness is measured on the continuous stream, as the frames overlap
+    self.loudness_meter.process(&buf);
+
+    // The buffer size of the source can be anything (and even change), so
+    // we collect the samples and analyze them in frames of a fixed size.
+    // Only the last frame is of interest to the renderers.
+    self.frame_buffer.push(&buf, self.channels);
+    let mut analyzed = None;
+    while let Some(frame) = self.frame_buffer.next_frame() {
+      let mut frame = self.analyze_frame(frame);
+      // Each frame ends with the samples of this buffer
+      frame.captured_at = Some(captured_at);
+      analyzed = Some(frame);
+    }
+    analyzed
+  }
+
+  fn analyze_frame (&mut self, buffers: Vec<Vec<f32>>) -> AnalyzedAudio {
+    // Perform the FFT on every channel
+    let mut output = Vec::with_capacity(buffers.len());
+    for buffer in buffers.iter() {
+      output.push(self.run_fft(buffer));
+    }
+
+    // The bins are spread over the full transform, including the padding
+    let bin_frequency = self.sample_rate as f32 / self.transform_size(self.fft_size) as f32;
+
+    let bands: Vec<Vec<f32>> = output.iter().map(|spectrum| group_bins(spectrum, bin_frequency, &self.bands)).collect();
+
+    // Every frame advances by one hop, which determines how far the
+    // smoothed values and peaks move
+    let elapsed = hop_size(self.fft_size, self.overlap) as f32 / self.sample_rate as f32;
+    self.frequency_smoothers.resize_with(output.len(), Smoother::create);
+    for (smoother, spectrum) in self.frequency_smoothers.iter_mut().zip(output.iter()) {
+      smoother.process(spectrum, elapsed, &self.ballistics);
+    }
+
+    // The beats are tracked on all channels combined
+    let mono_spectrum = downmix(&output);
+    let beat = self.beat_tracker.process(&mono_spectrum, elapsed);
+    if beat.beat {
+      self.beat_count += 1;
+    }
+
+    // The pitch is estimated on all channels combined as well
+    let mono_samples = downmix(&buffers);
+    let pitch = detect_pitch(&mono_samples, self.sample_rate, self.reference_tuning);
+
+    let features = self.feature_extractor.process(&mono_spectrum, bin_frequency, &mono_samples);
+
+    // The stereo image is the one of the channels we display
+    let [left, right] = self.routing.route(&buffers);
+    let stereo = analyze_stereo(&left, &right);
+
+    // The spectrum is already averaged, which is all the chroma needs
+    let chroma = fold_chroma(&mono_spectrum, bin_frequency, self.reference_tuning);
+
+    // Nothing but spectrum() reads the mel and constant-Q representations,
+    // so they are only computed while their view is selected
+    let (mel, mel_frequencies) = match self.view {
+      SpectrumView::Mel(_) => self.apply_mel_filterbank(&output, bin_frequency),
+      _ => (Vec::new(), Vec::new())
+    };
+    let (constant_q_spectra, constant_q_frequencies) = match self.view {
+      SpectrumView::ConstantQ => self.apply_constant_q(&buffers),
+      _ => (Vec::new(), Vec::new())
+    };
+
+    let mut analyzed = AnalyzedAudio {
+      amplitude: buffers,
+      frequency: output,
+      smoothed_frequency: self.frequency_smoothers.iter().map(|smoother| smoother.values()).collect(),
+      frequency_peaks: self.frequency_smoothers.iter().map(|smoother| smoother.peaks()).collect(),
+      bands,
+      band_frequencies: self.bands.iter().map(|band| band.center).collect(),
+      band_layout: self.band_layout,
+      mel,
+      mel_frequencies,
+      constant_q: constant_q_spectra,
+      constant_q_frequencies,
+      view: self.view,
+      smoothed_spectrum: Vec::new(), // See below
+      spectrum_peaks: Vec::new(),
+      routing: self.routing,
+      decibel_scale: self.decibel_scale,
+      ballistics: self.ballistics,
+      beat: beat.beat,
+      beat_count: self.beat_count,
+      onset_strength: beat.onset_strength,
+      bpm: beat.bpm,
+      beat_phase: beat.phase,
+      pitch,
+      chroma,
+      reference_tuning: self.reference_tuning,
+      loudness: self.loudness_meter.loudness().clone(),
+      stereo,
+      features,
+      sample_rate: self.sample_rate,
+      bin_frequency,
+      transform_size: self.transform_size(self.fft_size) as u32,
+      channels: self.channels,
+      buffer_size: self.fft_size as u32,
+      hop_size: hop_size(self.fft_size, self.overlap) as u32,
+      window: self.window,
+      captured_at: None // See analyze
+    };
+
+    // Only the selected view is smoothed
+    self.spectrum_smoothers.resize_with(analyzed.spectrum().len(), Smoother::create);
+    for (smoother, spectrum) in self.spectrum_smoothers.iter_mut().zip(analyzed.spectrum().iter()) {
+      smoother.process(spectrum, elapsed, &self.ballistics);
+    }
+    analyzed.smoothed_spectrum = self.spectrum_smoothers.iter().map(|smoother| smoother.values()).collect();
+    analyzed.spectrum_peaks = self.spectrum_smoothers.iter().map(|smoother| smoother.peaks()).collect();
+
+    analyzed
+  }
+
+  /// Passes the spectrum of every channel through the mel filterbank. Returns
+  /// the mel spectra and the center frequencies of their bands.
+  fn apply_mel_filterbank (&mut self, output: &[Vec<f32>], bin_frequency: f32) -> (Vec<Vec<f32>>, Vec<f32>) {
+    let bin_count = output[0].len();
+    let mel_filterbank = match self.mel_filterbank.take() {
+      Some(filterbank) if filterbank.matches(self.mel_band_count, bin_frequency, bin_count) => filterbank,
+      _ => MelFilterbank::create(self.mel_band_count, bin_frequency, bin_count)
+    };
+    let mel = output.iter().map(|spectrum| mel_filterbank.apply(spectrum)).collect();
+    let mel_frequencies = mel_filterbank.frequencies().to_vec();
+    self.mel_filterbank = Some(mel_filterbank);
+    (mel, mel_frequencies)
+  }
+
+  /// Runs the constant-Q transform, which needs the samples rather than the
+  /// spectrum, on every channel. Returns the spectra and the bin frequencies.
+  fn apply_constant_q (&mut self, buffers: &[Vec<f32>]) -> (Vec<Vec<f32>>, Vec<f32>) {
+    let constant_q = match self.constant_q.take() {
+      Some(transform) if transform.matches(self.sample_rate, self.fft_size, self.reference_tuning) => transform,
+      _ => ConstantQ::create(self.sample_rate, self.fft_size, self.reference_tuning)
+    };
+    let spectra = buffers.iter().map(|buffer| constant_q.process(buffer)).collect();
+    let frequencies = constant_q.frequencies().to_vec();
+    self.constant_q = Some(constant_q);
+    (spectra, frequencies)
+  }
+
+  /// Runs an FFT run on an audio buffer for one channel.
+  fn run_fft (&mut self, buf: &[f32]) -> Vec<f32> {
+    if self.window_coefficients.len() != buf.len() {
+      self.window_coefficients = self.window.coefficients(buf.len());
+    }
+
+    // Apply the window to the samples, which reduces the leakage into neighboring bins
+    let mut input: Vec<Complex<f32>> = Vec::new();
+    for (sample, coefficient) in buf.iter().zip(self.window_coefficients.iter()) {
+      input.push(Complex::from(*sample * *coefficient));
+    }
+
+    // Pad with zeroes if requested. This doesn't add information, but
+    // interpolates the spectrum and lets us use the fastest algorithms.
+    input.resize(self.transform_size(buf.len()), Complex::zero());
+
+    // The window attenuates the signal, so we need to make up for it. Scaled
+    // by 2 / sum of the window, a full scale sine ends up with an amplitude of
+    // 1.0 (the factor 2 b/c we drop the mirrored half of the spectrum).
+    // The padding doesn't change the sum, so this holds for padded frames, too.
+    let window_sum: f32 = self.window_coefficients.iter().sum();
+    let scale = 2.0 / window_sum;
+
+    // output must be equal to input, so we'll just prepopulate with zeroes
+    let mut output: Vec<Complex<f32>> = vec![Complex::zero(); input.len()];
+
+    // The planner picks the best algorithm for the length: Radix4 for powers
+    // of two, mixed radix for sizes such as 480, and Rader's algorithm for
+    // primes. So we can work with any buffer length.
+    let fft = self.planner.plan_fft(input.len());
+    fft.process(&mut input, &mut output); // Aaaaaand GO!
+
+    let mut output: Vec<f32> = output.iter().map(| el | {
+      // So, what we have in the output currently is a set of complex numbers which represent both the phase (which we
+      // don't need) and the magnitude/amplitude at the given frequency (which we want). There are multiple solutions, apparently,
+      // but what we actually want is sqrt(a^2 + b^2), so let's do that. Luckily, the complex number provides us with exactly that function:
+      el.norm_sqr().sqrt() * scale // Important: We have to call sqrt later on ourselves!
+    }).collect();
+
+    // For real input, the second half mirrors the first one, so we only keep
+    // the bins from DC up to and including the Nyquist frequency.
+    output.truncate(output.len() / 2 + 1);
+
+    // Cut off the 22,050Hz frequency (in case of 44.1kHz and 1,024 buffer size)
+    // as well as the DC point
+    output[0] = 0.0;
+    let last_idx = output.len() - 1;
+    output[last_idx] = 0.0;
+
+    // And return
+    output
+  }
+}
+
+/// Calculates how many samples to advance between two frames
+fn hop_size (fft_size: usize, overlap: f32) -> usize {
+  ((fft_size as f32 * (1.0 - overlap)).round() as usize).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::generator::Waveform;
+  use super::super::testing::{mono, stereo, assert_close};
+
+  fn peak (spectrum: &[f32]) -> (usize, f32) {
+    spectrum.iter().copied().enumerate().fold((0, 0.0), |peak, (idx, value)| if value > peak.1 { (idx, value) } else { peak })
+  }
+
+  #[test]
+  fn finds_the_bin_of_a_sine () {
+    let mut analyzer = Analyzer::create(44_100, 1);
+    // 1 kHz falls between bins, so the peak is the closest one
+    let bin_frequency: f32 = 44_100.0 / 2_048.0;
+    let spectrum = analyzer.run_fft(&mono(Waveform::Sine, 1_000.0, 0.5, 44_100, 2_048));
+    assert_eq!(spectrum.len(), 1_025);
+    assert_eq!(peak(&spectrum).0, (1_000.0 / bin_frequency).round() as usize);
+
+    // Any length works, with and without padding
+    let samples = mono(Waveform::Sine, 1_000.0, 0.5, 44_100, 1_500);
+    let spectrum = analyzer.run_fft(&samples);
+    assert_eq!(spectrum.len(), 751);
+    assert_eq!(peak(&spectrum).0, (1_000.0_f32 * 1_500.0 / 44_100.0).round() as usize);
+    analyzer.process(AnalyzerCommand::SetZeroPadding(true));
+    let spectrum = analyzer.run_fft(&samples);
+    assert_eq!(spectrum.len(), 1_025);
+    assert_eq!(peak(&spectrum).0, (1_000.0 / bin_frequency).round() as usize);
+  }
+
+  #[test]
+  fn compensates_the_window_amplitude () {
+    let mut analyzer = Analyzer::create(44_100, 1);
+    // Centered on bin 100, so there's no scalloping loss
+    let samples = mono(Waveform::Sine, 100.0 * 44_100.0 / 2_048.0, 0.5, 44_100, 2_048);
+    for window in WindowFunction::all() {
+      analyzer.process(AnalyzerCommand::SetWindow(window));
+      let (bin, amplitude) = peak(&analyzer.run_fft(&samples));
+      assert_eq!(bin, 100, "{}", window.name());
+      assert_close(amplitude, 0.5, 0.005);
+    }
+  }
+
+  #[test]
+  fn publishes_the_beat_on_the_frame_it_was_tracked () {
+    let mut analyzer = Analyzer::create(44_100, 2);
+    let hop = hop_size(DEFAULT_FFT_SIZE, DEFAULT_OVERLAP);
+    let mut beat_count = 0;
+    // A click track at 120 BPM, one hop at a time, so we see every frame
+    for chunk in stereo(Waveform::Click(120.0), 1_000.0, 0.8, 44_100, 44_100 * 4).chunks(hop * 2) {
+      if let Some(analyzed) = analyzer.process(AnalyzerCommand::Samples(chunk.to_vec(), Instant::now())) {
+        assert_eq!(analyzed.beat_count, if analyzed.beat { beat_count + 1 } else { beat_count });
+        beat_count = analyzed.beat_count;
+      }
+    }
+    assert!(beat_count > 0, "No beat tracked");
+  }
+}
@@ -0,0 +1,156 @@
+// Frequency bands -- groups the linear FFT bins into logarithmically spaced bands
+use super::util::format_frequency;
+
+// All layouts cover the audible range
+const MIN_FREQUENCY: f32 = 20.0;
+const MAX_FREQUENCY: f32 = 20_000.0;
+
+// Octave bands are centered around 1 kHz, as in ISO 266
+const REFERENCE_FREQUENCY: f32 = 1_000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BandLayout {
+  Octave(u32), // 1/n octave bands, e.g. Octave(3) for third octaves
+  Count(usize, f32, f32) // n bands, evenly spaced on a log scale between the min and max frequency
+}
+
+/// One frequency band
+#[derive(Clone, Copy, Debug)]
+pub struct Band {
+  pub lower: f32, // The lower edge in Hz
+  pub center: f32, // The (geometric) center in Hz
+  pub upper: f32 // The upper edge in Hz
+}
+
+impl BandLayout {
+  /// All band layouts, in the order the UI offers them
+  pub fn all () -> Vec<Self> {
+    vec![
+      BandLayout::Octave(1),
+      BandLayout::Octave(3),
+      BandLayout::Octave(6),
+      BandLayout::Octave(12),
+      BandLayout::Count(32, MIN_FREQUENCY, MAX_FREQUENCY),
+      BandLayout::Count(64, MIN_FREQUENCY, MAX_FREQUENCY),
+      BandLayout::Count(128, MIN_FREQUENCY, MAX_FREQUENCY)
+    ]
+  }
+
+  pub fn name (&self) -> String {
+    match self {
+      BandLayout::Octave(1) => String::from("Octaves"),
+      BandLayout::Octave(n) => format!("1/{} octaves", n),
+      BandLayout::Count(n, min, max) => format!("{} bars ({} to {})", n, format_frequency(*min), format_frequency(*max))
+    }
+  }
+
+  /// Calculates the bands of this layout
+  pub fn bands (&self) -> Vec<Band> {
+    match self {
+      BandLayout::Octave(n) => {
+        let n = (*n).max(1) as f32;
+        let half_width = 2_f32.powf(1.0 / (2.0 * n));
+        // Find the range of band indices (relative to the reference) we need
+        let first = (n * (MIN_FREQUENCY / REFERENCE_FREQUENCY).log2()).round() as i32;
+        let last = (n * (MAX_FREQUENCY / REFERENCE_FREQUENCY).log2()).round() as i32;
+        (first..=last).map(|k| {
+          let center = REFERENCE_FREQUENCY * 2_f32.powf(k as f32 / n);
+          Band { lower: center / half_width, center, upper: center * half_width }
+        }).collect()
+      },
+      BandLayout::Count(n, min, max) => {
+        let ratio = (max / min).powf(1.0 / *n as f32);
+        (0..*n).map(|k| {
+          let lower = min * ratio.powi(k as i32);
+          Band { lower, center: lower * ratio.sqrt(), upper: lower * ratio }
+        }).collect()
+      }
+    }
+  }
+}
+
+/// Groups a spectrum into the given bands. Each band gets the largest
+/// magnitude of its bins, so a pure tone keeps its level. Bands narrower than
+/// the bin spacing (which happens a lot in the bass) contain no bin at all,
+/// so they get the spectrum interpolated at their center.
+pub fn group_bins (spectrum: &[f32], bin_frequency: f32, bands: &[Band]) -> Vec<f32> {
+  if spectrum.is_empty() || bin_frequency <= 0.0 {
+    return vec![0.0; bands.len()];
+  }
+
+  bands.iter().map(|band| {
+    let first = (band.lower / bin_frequency).ceil() as usize;
+    let last = ((band.upper / bin_frequency).ceil() as usize).min(spectrum.len()); // exclusive
+
+    if first < last {
+      spectrum[first..last].iter().fold(0.0, |max: f32, value| max.max(*value))
+    } else {
+      // Linear interpolation between the two bins around the center
+      let position = band.center / bin_frequency;
+      let idx = position.floor() as usize;
+      if idx + 1 >= spectrum.len() {
+        0.0 // Beyond the Nyquist frequency
+      } else {
+        let fraction = position - idx as f32;
+        spectrum[idx] * (1.0 - fraction) + spectrum[idx + 1] * fraction
+      }
+    }
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::testing::assert_close;
+
+  // Band edges are products of powers, so they match up to rounding
+  fn assert_same_frequency (value: f32, expected: f32) {
+    assert_close(value, expected, expected * 1e-4);
+  }
+
+  #[test]
+  fn centers_octave_bands_on_1_khz () {
+    for n in [1, 3, 12] {
+      let bands = BandLayout::Octave(n).bands();
+      let reference = bands.iter().find(|band| (band.center - REFERENCE_FREQUENCY).abs() < 0.01).expect("No band at 1 kHz");
+      assert_same_frequency(reference.upper / reference.lower, 2_f32.powf(1.0 / n as f32));
+      // The outermost bands contain the limits of the audible range
+      assert!(bands[0].lower <= MIN_FREQUENCY && bands[0].upper >= MIN_FREQUENCY);
+      assert!(bands[bands.len() - 1].lower <= MAX_FREQUENCY && bands[bands.len() - 1].upper >= MAX_FREQUENCY);
+    }
+    // The standard octave centers (nominally 16 Hz to 16 kHz)
+    let centers: Vec<f32> = BandLayout::Octave(1).bands().iter().map(|band| band.center).collect();
+    assert_eq!(centers.len(), 11);
+    assert_same_frequency(centers[1], 31.25);
+    assert_same_frequency(centers[10], 16_000.0);
+  }
+
+  #[test]
+  fn spans_the_range_without_gaps () {
+    for layout in BandLayout::all() {
+      let bands = layout.bands();
+      for pair in bands.windows(2) {
+        assert_same_frequency(pair[1].lower, pair[0].upper);
+        assert!(pair[0].lower < pair[0].center && pair[0].center < pair[0].upper);
+      }
+    }
+
+    let bands = BandLayout::Count(32, 20.0, 20_000.0).bands();
+    assert_eq!(bands.len(), 32);
+    assert_same_frequency(bands[0].lower, 20.0);
+    assert_same_frequency(bands[31].upper, 20_000.0);
+  }
+
+  #[test]
+  fn groups_bins_into_bands () {
+    let bands = [
+      Band { lower: 8.0, center: 16.0, upper: 32.0 },
+      Band { lower: 32.0, center: 36.0, upper: 40.0 }
+    ];
+    // Bins every 10 Hz. The first band holds bins 1 to 3 (10 to 30 Hz), the
+    // second none, so it's interpolated.
+    let grouped = group_bins(&[0.0, 0.5, 0.25, 1.0, 0.0], 10.0, &bands);
+    assert_eq!(grouped[0], 1.0);
+    assert_close(grouped[1], 0.4, 1e-6);
+  }
+}
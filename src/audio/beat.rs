@@ -0,0 +1,208 @@
+// Beat detection -- onsets via spectral flux, plus tempo and phase tracking
+use std::collections::VecDeque;
+
+// How much onset history we keep to estimate the tempo, in seconds
+const HISTORY_LENGTH: f32 = 8.0;
+// We need at least this much history before we trust the tempo
+const MIN_HISTORY_LENGTH: f32 = 3.0;
+// The range of the adaptive onset threshold, in seconds
+const THRESHOLD_LENGTH: f32 = 0.5;
+// An onset needs to exceed the local average flux by this factor
+const THRESHOLD_FACTOR: f32 = 1.5;
+// No two onsets are closer than this (in seconds), which avoids double triggers
+const MIN_ONSET_INTERVAL: f32 = 0.1;
+// How often we re-estimate the tempo, in seconds
+const TEMPO_INTERVAL: f32 = 0.5;
+// The range of tempi we look for
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 180.0;
+// The autocorrelation is weighted towards this tempo, so we don't lock onto
+// half or double the tempo of most music
+const PREFERRED_BPM: f32 = 120.0;
+// How strongly an onset pulls the beat phase towards it
+const PHASE_CORRECTION: f32 = 0.2;
+// Compression applied to the magnitudes before calculating the flux, so quiet
+// onsets count as well
+const COMPRESSION: f32 = 100.0;
+
+/// Detects onsets in a stream of spectra and tracks the tempo and the beat
+/// phase from them.
+pub struct BeatTracker {
+  previous_spectrum: Vec<f32>, // Log compressed
+  flux_history: VecDeque<f32>, // The onset strength of the last frames
+  frame_duration: f32, // Seconds between two frames
+  since_onset: f32, // Seconds since the last onset
+  since_tempo: f32, // Seconds since we estimated the tempo
+  bpm: f32, // 0.0 as long as we don't know the tempo
+  phase: f32 // 0.0 (on the beat) to 1.0 (just before the next one)
+}
+
+/// What the tracker found in a single frame
+pub struct BeatState {
+  pub beat: bool, // True if a beat falls onto this frame
+  pub onset_strength: f32, // The spectral flux relative to the recent maximum, 0.0 to 1.0
+  pub bpm: f32, // The estimated tempo, 0.0 if unknown
+  pub phase: f32 // The position within the current beat, 0.0 to 1.0
+}
+
+impl BeatTracker {
+  pub fn create () -> Self {
+    Self {
+      previous_spectrum: Vec::new(),
+      flux_history: VecDeque::new(),
+      frame_duration: 0.0,
+      since_onset: 0.0,
+      since_tempo: 0.0,
+      bpm: 0.0,
+      phase: 0.0
+    }
+  }
+
+  /// Feeds the (mono) magnitude spectrum of the next frame, which arrived
+  /// `elapsed` seconds after the previous one.
+  pub fn process (&mut self, spectrum: &[f32], elapsed: f32) -> BeatState {
+    if (elapsed - self.frame_duration).abs() > f32::EPSILON {
+      // The frame rate changed, which invalidates the history
+      self.reset();
+      self.frame_duration = elapsed;
+    }
+
+    let flux = self.spectral_flux(spectrum);
+    let onset = self.detect_onset(flux);
+
+    let max_frames = (HISTORY_LENGTH / elapsed).ceil() as usize;
+    self.flux_history.push_back(flux);
+    while self.flux_history.len() > max_frames {
+      self.flux_history.pop_front();
+    }
+
+    self.since_tempo += elapsed;
+    if self.since_tempo >= TEMPO_INTERVAL {
+      self.since_tempo = 0.0;
+      self.bpm = self.estimate_tempo();
+    }
+
+    // Advance the beat clock, and nudge it towards the onsets
+    let mut beat = false;
+    if self.bpm > 0.0 {
+      self.phase += elapsed * self.bpm / 60.0;
+      if onset {
+        // Pull the phase towards the closest beat (0.0 or 1.0)
+        let error = if self.phase > 0.5 { self.phase - 1.0 } else { self.phase };
+        self.phase -= error * PHASE_CORRECTION;
+      }
+      if self.phase >= 1.0 {
+        self.phase = self.phase.fract();
+        beat = true;
+      }
+    } else {
+      // Without a tempo, every onset is a beat
+      beat = onset;
+      self.phase = 0.0;
+    }
+
+    let max_flux = self.flux_history.iter().fold(0.0, |max: f32, value| max.max(*value));
+
+    BeatState {
+      beat,
+      onset_strength: if max_flux > 0.0 { flux / max_flux } else { 0.0 },
+      bpm: self.bpm,
+      phase: self.phase
+    }
+  }
+
+  /// Forgets everything, e.g. after switching to a different source
+  pub fn reset (&mut self) {
+    self.previous_spectrum.clear();
+    self.flux_history.clear();
+    self.since_onset = 0.0;
+    self.since_tempo = 0.0;
+    self.bpm = 0.0;
+    self.phase = 0.0;
+  }
+
+  /// Sums up how much the (compressed) magnitudes increased since the last
+  /// frame. Decreases don't count, since only new sounds are onsets.
+  fn spectral_flux (&mut self, spectrum: &[f32]) -> f32 {
+    let compressed: Vec<f32> = spectrum.iter().map(|magnitude| (1.0 + COMPRESSION * magnitude).ln()).collect();
+
+    let mut flux = 0.0;
+    if compressed.len() == self.previous_spectrum.len() && !compressed.is_empty() {
+      for (current, previous) in compressed.iter().zip(self.previous_spectrum.iter()) {
+        flux += (current - previous).max(0.0);
+      }
+      flux /= compressed.len() as f32;
+    }
+
+    self.previous_spectrum = compressed;
+    flux
+  }
+
+  /// Compares the flux against the average of the last frames
+  fn detect_onset (&mut self, flux: f32) -> bool {
+    self.since_onset += self.frame_duration;
+
+    let threshold_frames = ((THRESHOLD_LENGTH / self.frame_duration).ceil() as usize).max(1);
+    let recent: Vec<&f32> = self.flux_history.iter().rev().take(threshold_frames).collect();
+    if recent.is_empty() {
+      return false;
+    }
+
+    let average = recent.iter().copied().sum::<f32>() / recent.len() as f32;
+    if flux > average * THRESHOLD_FACTOR && flux > 0.0 && self.since_onset >= MIN_ONSET_INTERVAL {
+      self.since_onset = 0.0;
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Finds the most likely beat period in the onset history using its
+  /// autocorrelation. Returns 0.0 if there isn't enough history (or rhythm).
+  fn estimate_tempo (&self) -> f32 {
+    let frame_rate = 1.0 / self.frame_duration;
+    if (self.flux_history.len() as f32) < MIN_HISTORY_LENGTH * frame_rate {
+      return 0.0;
+    }
+
+    // Remove the mean, so a constant level doesn't correlate
+    let mean = self.flux_history.iter().sum::<f32>() / self.flux_history.len() as f32;
+    let history: Vec<f32> = self.flux_history.iter().map(|flux| flux - mean).collect();
+
+    let min_lag = ((60.0 / MAX_BPM * frame_rate).floor() as usize).max(1);
+    let max_lag = ((60.0 / MIN_BPM * frame_rate).ceil() as usize).min(history.len() - 2);
+    if min_lag + 2 > max_lag {
+      return 0.0; // The frame rate is too low for this range
+    }
+
+    let correlation: Vec<f32> = (min_lag - 1..=max_lag + 1).map(|lag| {
+      let sum: f32 = history.iter().zip(history.iter().skip(lag)).map(|(a, b)| a * b).sum();
+      sum / (history.len() - lag) as f32
+    }).collect();
+
+    // Find the best lag (correlation[0] and the last element are only needed
+    // for the interpolation)
+    let mut best_idx = 0;
+    let mut best_value = 0.0;
+    for (idx, value) in correlation.iter().enumerate().take(correlation.len() - 1).skip(1) {
+      let bpm = 60.0 * frame_rate / (min_lag + idx - 1) as f32;
+      let weight = (-0.5 * (bpm / PREFERRED_BPM).log2().powi(2)).exp();
+      if value * weight > best_value {
+        best_value = value * weight;
+        best_idx = idx;
+      }
+    }
+
+    if best_idx == 0 {
+      return 0.0; // Nothing periodic in there
+    }
+
+    // Interpolate the peak with a parabola for a finer tempo resolution
+    let (left, center, right) = (correlation[best_idx - 1], correlation[best_idx], correlation[best_idx + 1]);
+    let denominator = left - 2.0 * center + right;
+    let offset = if denominator.abs() > f32::EPSILON { 0.5 * (left - right) / denominator } else { 0.0 };
+    let lag = (min_lag + best_idx - 1) as f32 + offset.clamp(-0.5, 0.5);
+
+    60.0 * frame_rate / lag
+  }
+}
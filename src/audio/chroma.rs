@@ -0,0 +1,45 @@
+// Chroma -- folds the spectrum into the twelve pitch classes of equal temperament
+
+/// The reference tunings (the frequency of A4) offered in the UI
+pub const REFERENCE_TUNINGS: [f32; 5] = [415.0, 432.0, 440.0, 442.0, 444.0];
+pub const DEFAULT_REFERENCE_TUNING: f32 = 440.0;
+
+/// The names of the pitch classes, starting with C
+pub const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+// Only bins in this range count. Below it, a single bin spans several
+// semitones, and above it there are mostly overtones and noise.
+const MIN_FREQUENCY: f32 = 60.0;
+const MAX_FREQUENCY: f32 = 5_000.0;
+
+/// Converts a frequency into a (fractional) MIDI note number, which puts A4
+/// at 69 and C4 at 60.
+pub fn midi_note (frequency: f32, reference_tuning: f32) -> f32 {
+  69.0 + 12.0 * (frequency / reference_tuning).log2()
+}
+
+/// Sums up the energy of all bins per pitch class (0 is C, 11 is B). The
+/// result is normalized, so the strongest class is 1.0, unless everything
+/// is silent.
+pub fn fold_chroma (spectrum: &[f32], bin_frequency: f32, reference_tuning: f32) -> [f32; 12] {
+  let mut chroma = [0.0; 12];
+  if bin_frequency <= 0.0 {
+    return chroma;
+  }
+
+  let first = (MIN_FREQUENCY / bin_frequency).ceil() as usize;
+  let last = ((MAX_FREQUENCY / bin_frequency).floor() as usize).min(spectrum.len().saturating_sub(1));
+  for (idx, magnitude) in spectrum.iter().enumerate().take(last + 1).skip(first) {
+    let pitch_class = midi_note(idx as f32 * bin_frequency, reference_tuning).round() as i32;
+    chroma[pitch_class.rem_euclid(12) as usize] += magnitude * magnitude;
+  }
+
+  let max = chroma.iter().fold(0.0, |max: f32, value| max.max(*value));
+  if max > 0.0 {
+    for value in chroma.iter_mut() {
+      *value /= max;
+    }
+  }
+
+  chroma
+}
@@ -0,0 +1,95 @@
+// Constant-Q transform -- analyzes the frame with one bin per semitone and a constant ratio of frequency to bandwidth
+use std::f32::consts::PI;
+
+// The bins are spaced in semitones
+const BINS_PER_OCTAVE: usize = 12;
+// The lowest bin sits on C1 (about 32.7 Hz), the highest one on C9
+const MIN_NOTE: f32 = 24.0;
+const MAX_NOTE: f32 = 120.0;
+
+/// The (windowed) complex exponential one bin is correlated with
+struct Kernel {
+  offset: usize, // Where within the frame the kernel starts
+  cos: Vec<f32>,
+  sin: Vec<f32>
+}
+
+/// Correlates the frame with one kernel per semitone. The length of each
+/// kernel is inversely proportional to its frequency, which gives the low
+/// bins a much finer frequency resolution than the FFT, and the high bins a
+/// much finer time resolution. The low kernels are capped at the frame size,
+/// so their resolution depends on the FFT size.
+pub struct ConstantQ {
+  kernels: Vec<Kernel>,
+  frequencies: Vec<f32>, // The center frequency of each bin
+  sample_rate: u32,
+  frame_size: usize,
+  reference_tuning: f32
+}
+
+impl ConstantQ {
+  pub fn create (sample_rate: u32, frame_size: usize, reference_tuning: f32) -> Self {
+    let rate = sample_rate as f32;
+    let q = 1.0 / (2_f32.powf(1.0 / BINS_PER_OCTAVE as f32) - 1.0);
+
+    let mut kernels = Vec::new();
+    let mut frequencies = Vec::new();
+    let bin_count = ((MAX_NOTE - MIN_NOTE) as usize) * BINS_PER_OCTAVE / 12;
+    for k in 0..=bin_count {
+      // The notes are MIDI note numbers, relative to the reference tuning (A4 = 69)
+      let note = MIN_NOTE + k as f32 * 12.0 / BINS_PER_OCTAVE as f32;
+      let frequency = reference_tuning * 2_f32.powf((note - 69.0) / 12.0);
+      if frequency * (1.0 + 0.5 / q) >= rate / 2.0 {
+        break; // The bin would reach beyond the Nyquist frequency
+      }
+
+      let length = ((q * rate / frequency).ceil() as usize).clamp(1, frame_size.max(1));
+      let window: Vec<f32> = (0..length).map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / length as f32).cos()).collect();
+      // Just as with the FFT, a full scale sine should end up at 1.0
+      let scale = 2.0 / window.iter().sum::<f32>().max(f32::EPSILON);
+
+      let mut cos = Vec::with_capacity(length);
+      let mut sin = Vec::with_capacity(length);
+      for (n, coefficient) in window.iter().enumerate() {
+        let phase = 2.0 * PI * frequency * n as f32 / rate;
+        cos.push(coefficient * phase.cos() * scale);
+        sin.push(coefficient * phase.sin() * scale);
+      }
+
+      // Center all kernels within the frame
+      kernels.push(Kernel { offset: (frame_size - length) / 2, cos, sin });
+      frequencies.push(frequency);
+    }
+
+    Self { kernels, frequencies, sample_rate, frame_size, reference_tuning }
+  }
+
+  /// Returns true if the transform was made for these parameters
+  pub fn matches (&self, sample_rate: u32, frame_size: usize, reference_tuning: f32) -> bool {
+    self.sample_rate == sample_rate && self.frame_size == frame_size && self.reference_tuning == reference_tuning
+  }
+
+  /// The center frequency of each bin
+  pub fn frequencies (&self) -> &[f32] {
+    &self.frequencies
+  }
+
+  /// Calculates the magnitude of each bin for a frame of samples
+  pub fn process (&self, frame: &[f32]) -> Vec<f32> {
+    self.kernels.iter().map(|kernel| {
+      if kernel.offset + kernel.cos.len() > frame.len() {
+        return 0.0;
+      }
+
+      let samples = &frame[kernel.offset..kernel.offset + kernel.cos.len()];
+      let mut real = 0.0;
+      let mut imaginary = 0.0;
+      for ((sample, cos), sin) in samples.iter().zip(kernel.cos.iter()).zip(kernel.sin.iter()) {
+        real += sample * cos;
+        imaginary += sample * sin;
+      }
+      (real * real + imaginary * imaginary).sqrt()
+    }).collect()
+  }
+}
+
@@ -0,0 +1,49 @@
+// Decibel scaling -- maps magnitudes onto a fixed dBFS range for display
+
+/// The ranges offered in the UI as (floor, ceiling) in dBFS
+pub const DECIBEL_RANGES: [(f32, f32); 5] = [
+  (-60.0, 0.0),
+  (-72.0, 0.0),
+  (-90.0, 0.0),
+  (-120.0, 0.0),
+  (-90.0, -20.0) // For quiet material
+];
+
+// Anything below this is treated as silence, which avoids log10(0)
+const MIN_MAGNITUDE: f32 = 1e-10;
+
+/// Describes which part of the dBFS scale is displayed. Magnitudes at the
+/// ceiling (0 dBFS = a full scale sine) or above fill the full height,
+/// everything at the floor or below is not displayed at all.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecibelScale {
+  pub floor: f32, // in dBFS, e.g. -90.0
+  pub ceiling: f32 // in dBFS, usually 0.0
+}
+
+impl DecibelScale {
+  pub fn create (floor: f32, ceiling: f32) -> Self {
+    Self { floor, ceiling }
+  }
+
+  pub fn name (&self) -> String {
+    format!("{} to {} dB", self.floor, self.ceiling)
+  }
+
+  /// Converts a linear magnitude (1.0 = full scale) into dBFS
+  pub fn to_decibels (magnitude: f32) -> f32 {
+    20.0 * magnitude.abs().max(MIN_MAGNITUDE).log10()
+  }
+
+  /// Maps a linear magnitude onto 0.0 (floor) to 1.0 (ceiling)
+  pub fn normalize (&self, magnitude: f32) -> f32 {
+    let decibels = Self::to_decibels(magnitude);
+    ((decibels - self.floor) / (self.ceiling - self.floor)).clamp(0.0, 1.0)
+  }
+}
+
+impl Default for DecibelScale {
+  fn default () -> Self {
+    Self::create(-90.0, 0.0)
+  }
+}
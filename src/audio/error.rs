@@ -0,0 +1,98 @@
+// Audio errors -- everything that can go wrong while opening or listening to a source
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub enum AudioError {
+  NoDevice, // The host doesn't have a single input device
+  Host(String), // The host is unavailable or couldn't list its devices
+  Device(String), // The device couldn't be queried, e.g. because it has been unplugged
+  Config(String), // The device doesn't support any (or the requested) configuration
+  Stream(String), // The stream couldn't be built or started, or it failed while running
+  Source(String), // Any other source couldn't be opened, e.g. a missing file
+  Disconnected // The source stopped delivering samples
+}
+
+impl fmt::Display for AudioError {
+  fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AudioError::NoDevice => write!(f, "No input device available"),
+      AudioError::Host(e) => write!(f, "The audio host is not available: {}", e),
+      AudioError::Device(e) => write!(f, "The input device is not available: {}", e),
+      AudioError::Config(e) => write!(f, "Unsupported stream configuration: {}", e),
+      AudioError::Stream(e) => write!(f, "The input stream failed: {}", e),
+      AudioError::Source(e) => write!(f, "Could not open the source: {}", e),
+      AudioError::Disconnected => write!(f, "The source stopped delivering samples")
+    }
+  }
+}
+
+impl std::error::Error for AudioError {}
+
+// Conversions from the errors of cpal, so we can use the ? operator
+
+impl From<cpal::HostUnavailable> for AudioError {
+  fn from (e: cpal::HostUnavailable) -> Self {
+    AudioError::Host(e.to_string())
+  }
+}
+
+impl From<cpal::DevicesError> for AudioError {
+  fn from (e: cpal::DevicesError) -> Self {
+    AudioError::Host(e.to_string())
+  }
+}
+
+impl From<cpal::DeviceNameError> for AudioError {
+  fn from (e: cpal::DeviceNameError) -> Self {
+    AudioError::Device(e.to_string())
+  }
+}
+
+impl From<cpal::SupportedStreamConfigsError> for AudioError {
+  fn from (e: cpal::SupportedStreamConfigsError) -> Self {
+    AudioError::Device(e.to_string())
+  }
+}
+
+impl From<cpal::DefaultStreamConfigError> for AudioError {
+  fn from (e: cpal::DefaultStreamConfigError) -> Self {
+    AudioError::Config(e.to_string())
+  }
+}
+
+impl From<cpal::BuildStreamError> for AudioError {
+  fn from (e: cpal::BuildStreamError) -> Self {
+    match e {
+      cpal::BuildStreamError::DeviceNotAvailable => AudioError::Device(e.to_string()),
+      cpal::BuildStreamError::StreamConfigNotSupported => AudioError::Config(e.to_string()),
+      _ => AudioError::Stream(e.to_string())
+    }
+  }
+}
+
+impl From<cpal::PlayStreamError> for AudioError {
+  fn from (e: cpal::PlayStreamError) -> Self {
+    match e {
+      cpal::PlayStreamError::DeviceNotAvailable => AudioError::Device(e.to_string()),
+      _ => AudioError::Stream(e.to_string())
+    }
+  }
+}
+
+impl From<cpal::PauseStreamError> for AudioError {
+  fn from (e: cpal::PauseStreamError) -> Self {
+    match e {
+      cpal::PauseStreamError::DeviceNotAvailable => AudioError::Device(e.to_string()),
+      _ => AudioError::Stream(e.to_string())
+    }
+  }
+}
+
+impl From<cpal::StreamError> for AudioError {
+  fn from (e: cpal::StreamError) -> Self {
+    match e {
+      cpal::StreamError::DeviceNotAvailable => AudioError::Device(e.to_string()),
+      _ => AudioError::Stream(e.to_string())
+    }
+  }
+}
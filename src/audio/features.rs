@@ -0,0 +1,128 @@
+// Spectral features -- descriptors of the timbre of a frame, such as its brightness and noisiness
+
+// The share of the energy that lies below the rolloff frequency
+const ROLLOFF_SHARE: f32 = 0.85;
+// The brightness maps the centroid between these frequencies (log-spaced) to 0.0-1.0
+const MIN_BRIGHTNESS_FREQUENCY: f32 = 100.0;
+const MAX_BRIGHTNESS_FREQUENCY: f32 = 8_000.0;
+
+/// The features of a single frame. All frequencies are in Hz, and all of them
+/// are 0.0 for silent frames.
+#[derive(Clone, Copy)]
+pub struct SpectralFeatures {
+  pub centroid: f32, // The center of mass of the spectrum, which correlates with its brightness
+  pub spread: f32, // The standard deviation of the spectrum around its centroid
+  pub flux: f32, // How much the shape of the spectrum changed since the last frame, 0.0 to 1.0
+  pub rolloff: f32, // The frequency below which 85 % of the energy lie
+  pub flatness: f32, // 0.0 for a pure tone to 1.0 for white noise
+  pub zero_crossing_rate: f32 // The share of samples at which the signal changes its sign, 0.0 to 1.0
+}
+
+impl SpectralFeatures {
+  pub fn create () -> Self {
+    Self {
+      centroid: 0.0,
+      spread: 0.0,
+      flux: 0.0,
+      rolloff: 0.0,
+      flatness: 0.0,
+      zero_crossing_rate: 0.0
+    }
+  }
+
+  /// The centroid on a log scale from 0.0 (100 Hz or below) to 1.0 (8 kHz or above)
+  pub fn brightness (&self) -> f32 {
+    if self.centroid <= 0.0 {
+      return 0.0;
+    }
+
+    let range = (MAX_BRIGHTNESS_FREQUENCY / MIN_BRIGHTNESS_FREQUENCY).ln();
+    ((self.centroid / MIN_BRIGHTNESS_FREQUENCY).ln() / range).clamp(0.0, 1.0)
+  }
+}
+
+/// Calculates the features frame by frame. It remembers the last spectrum,
+/// which the flux is measured against.
+pub struct FeatureExtractor {
+  previous_distribution: Vec<f32> // The last spectrum, normalized to a sum of 1.0
+}
+
+impl FeatureExtractor {
+  pub fn create () -> Self {
+    Self {
+      previous_distribution: Vec::new()
+    }
+  }
+
+  /// Forgets the last spectrum, e.g. when the source changes
+  pub fn reset (&mut self) {
+    self.previous_distribution.clear();
+  }
+
+  /// Analyzes a magnitude spectrum and the samples it was calculated from
+  pub fn process (&mut self, spectrum: &[f32], bin_frequency: f32, samples: &[f32]) -> SpectralFeatures {
+    let mut features = SpectralFeatures::create();
+    features.zero_crossing_rate = zero_crossing_rate(samples);
+
+    let power: Vec<f32> = spectrum.iter().map(|magnitude| magnitude * magnitude).collect();
+    let total: f32 = power.iter().sum();
+    if total <= 0.0 || bin_frequency <= 0.0 {
+      self.previous_distribution.clear();
+      return features;
+    }
+
+    // The spectrum as a probability distribution over the frequencies
+    let distribution: Vec<f32> = power.iter().map(|value| value / total).collect();
+
+    features.centroid = distribution.iter().enumerate()
+      .map(|(bin, share)| bin as f32 * bin_frequency * share)
+      .sum();
+    let variance: f32 = distribution.iter().enumerate()
+      .map(|(bin, share)| (bin as f32 * bin_frequency - features.centroid).powi(2) * share)
+      .sum();
+    features.spread = variance.sqrt();
+
+    let mut cumulative = 0.0;
+    for (bin, share) in distribution.iter().enumerate() {
+      cumulative += share;
+      if cumulative >= ROLLOFF_SHARE {
+        features.rolloff = bin as f32 * bin_frequency;
+        break;
+      }
+    }
+
+    // The ratio of the geometric to the arithmetic mean of the power. The DC
+    // bin is left out, and empty bins would make the geometric mean zero.
+    let bins = &power[1.min(power.len())..];
+    if !bins.is_empty() {
+      let log_sum: f32 = bins.iter().map(|value| value.max(f32::MIN_POSITIVE).ln()).sum();
+      let geometric_mean = (log_sum / bins.len() as f32).exp();
+      let arithmetic_mean = bins.iter().sum::<f32>() / bins.len() as f32;
+      if arithmetic_mean > 0.0 {
+        features.flatness = (geometric_mean / arithmetic_mean).clamp(0.0, 1.0);
+      }
+    }
+
+    // Half the distance between both distributions, so the flux ignores the
+    // overall level and only reacts to changes of the timbre
+    if self.previous_distribution.len() == distribution.len() {
+      let distance: f32 = distribution.iter().zip(self.previous_distribution.iter())
+        .map(|(current, previous)| (current - previous).abs())
+        .sum();
+      features.flux = (distance / 2.0).clamp(0.0, 1.0);
+    }
+    self.previous_distribution = distribution;
+
+    features
+  }
+}
+
+/// The share of neighboring samples with opposite signs
+fn zero_crossing_rate (samples: &[f32]) -> f32 {
+  if samples.len() < 2 {
+    return 0.0;
+  }
+
+  let crossings = samples.windows(2).filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0)).count();
+  crossings as f32 / (samples.len() - 1) as f32
+}
@@ -0,0 +1,134 @@
+// File playback -- decodes an audio file and feeds it to the analyzer in real time
+use std::fs::File;
+use std::path::Path;
+use std::time::Instant;
+use super::source::{ChannelSource, Pacer, CHUNK_FRAMES};
+
+// Decoding imports
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatReader, FormatOptions};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Wraps the demuxer and decoder of an opened audio file
+struct FileDecoder {
+  format: Box<dyn FormatReader>,
+  decoder: Box<dyn Decoder>,
+  track_id: u32,
+  sample_rate: u32,
+  channels: usize
+}
+
+impl FileDecoder {
+  fn open (path: &Path) -> Result<Self, String> {
+    let file = File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    // Help the probe by telling it the file extension
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+      hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+      .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+      .map_err(|e| format!("Unsupported file {}: {}", path.display(), e))?;
+
+    let format = probed.format;
+
+    // We only play the first track that we can actually decode
+    let track = format.tracks().iter().find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+      .ok_or_else(|| format!("No audio track found in {}", path.display()))?;
+
+    let sample_rate = track.codec_params.sample_rate
+      .ok_or_else(|| format!("Unknown sample rate in {}", path.display()))?;
+    let channels = track.codec_params.channels
+      .ok_or_else(|| format!("Unknown channel layout in {}", path.display()))?.count();
+    let track_id = track.id;
+
+    let decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())
+      .map_err(|e| format!("Unsupported codec in {}: {}", path.display(), e))?;
+
+    Ok(Self { format, decoder, track_id, sample_rate, channels })
+  }
+
+  /// Returns the next batch of interleaved samples, or None at the end of the file
+  fn next_samples (&mut self) -> Option<Vec<f32>> {
+    loop {
+      let packet = match self.format.next_packet() {
+        Ok(packet) => packet,
+        Err(_) => return None // End of stream (or an unrecoverable error, which amounts to the same)
+      };
+
+      if packet.track_id() != self.track_id {
+        continue;
+      }
+
+      match self.decoder.decode(&packet) {
+        Ok(decoded) => {
+          let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+          buffer.copy_interleaved_ref(decoded);
+          return Some(buffer.samples().to_vec());
+        },
+        Err(Error::DecodeError(_)) => continue, // Corrupt packet, just skip it
+        Err(_) => return None
+      }
+    }
+  }
+}
+
+/// Opens an audio file and plays it back in real time (looping at the end).
+pub fn create_file_source (path: &Path) -> Result<ChannelSource, String> {
+  // Open the file already here so that we can report errors and the format
+  let mut decoder = FileDecoder::open(path)?;
+  let path = path.to_path_buf();
+  let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+  let (sample_rate, channels) = (decoder.sample_rate, decoder.channels);
+
+  println!("Playing back file {} ...", path.display());
+
+  Ok(ChannelSource::spawn(format!("File: {}", name), sample_rate, channels, move |tx, state| {
+    let chunk_len = CHUNK_FRAMES * channels;
+    let mut pending: Vec<f32> = Vec::new();
+    let mut pacer = Pacer::create(sample_rate);
+
+    while !state.is_stopped() {
+      if !state.is_playing() {
+        pacer.idle();
+        continue;
+      }
+
+      // Decode until we have enough for a full chunk
+      while pending.len() < chunk_len {
+        match decoder.next_samples() {
+          Some(samples) => pending.extend(samples),
+          None => {
+            // End of file: Start over from the beginning
+            match FileDecoder::open(&path) {
+              Ok(new_decoder) => decoder = new_decoder,
+              Err(e) => {
+                println!("ERROR: {}", e);
+                return;
+              }
+            }
+            // A file without any samples would keep us busy forever
+            if pending.is_empty() && decoder.next_samples().map(|s| pending.extend(s)).is_none() {
+              return;
+            }
+          }
+        }
+      }
+
+      // Wait until the chunk is due to keep up the real time pace
+      pacer.wait();
+
+      let chunk: Vec<f32> = pending.drain(0..chunk_len).collect();
+      if tx.send((chunk, Instant::now())).is_err() {
+        return; // Nobody's listening anymore
+      }
+    }
+  }))
+}
@@ -0,0 +1,113 @@
+// Frame buffer -- turns incoming buffers of any size into overlapping FFT frames
+use std::collections::VecDeque;
+
+/// The FFT sizes offered in the UI
+pub const FFT_SIZES: [usize; 6] = [512, 1_024, 2_048, 4_096, 8_192, 16_384];
+
+/// The overlaps between two consecutive frames offered in the UI
+pub const OVERLAPS: [f32; 4] = [0.0, 0.5, 0.75, 0.875];
+
+/// Accumulates the samples of all channels and hands out frames of a fixed
+/// size. After each frame, it advances by the hop size, so the frames
+/// overlap whenever the hop size is smaller than the frame size.
+pub struct FrameBuffer {
+  frame_size: usize,
+  hop_size: usize,
+  samples: Vec<VecDeque<f32>> // One queue per channel
+}
+
+impl FrameBuffer {
+  pub fn create (frame_size: usize, hop_size: usize) -> Self {
+    Self {
+      frame_size,
+      hop_size: hop_size.clamp(1, frame_size),
+      samples: Vec::new()
+    }
+  }
+
+  /// Adds an interleaved buffer with the given amount of channels. If the
+  /// amount changes, the samples collected so far are dropped.
+  pub fn push (&mut self, buf: &[f32], channels: usize) {
+    if channels == 0 {
+      return;
+    }
+
+    if self.samples.len() != channels {
+      self.samples = vec![VecDeque::new(); channels];
+    }
+
+    // NOTE: This buffer is INTERLEAVED, that means LRLRLRLRLRLR ...!!!
+    for frame in buf.chunks_exact(channels) {
+      for (samples, sample) in self.samples.iter_mut().zip(frame.iter()) {
+        samples.push_back(*sample);
+      }
+    }
+  }
+
+  /// Returns the next complete frame for all channels, if there is one
+  pub fn next_frame (&mut self) -> Option<Vec<Vec<f32>>> {
+    if self.samples.is_empty() || self.samples[0].len() < self.frame_size {
+      return None;
+    }
+
+    let mut frame = Vec::with_capacity(self.samples.len());
+    for samples in self.samples.iter_mut() {
+      frame.push(samples.iter().take(self.frame_size).copied().collect());
+      samples.drain(0..self.hop_size);
+    }
+
+    Some(frame)
+  }
+
+  /// Throws away all samples, e.g. after switching to a different source
+  pub fn clear (&mut self) {
+    self.samples.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Two channels counting up from the start, with the right one negated
+  fn counting (range: std::ops::Range<usize>) -> Vec<f32> {
+    range.flat_map(|idx| vec![idx as f32, -(idx as f32)]).collect()
+  }
+
+  fn ramp (range: std::ops::Range<usize>) -> Vec<f32> {
+    range.map(|idx| idx as f32).collect()
+  }
+
+  #[test]
+  fn overlaps_frames_by_the_hop_size () {
+    let mut frames = FrameBuffer::create(8, 4);
+    frames.push(&counting(0..10), 2);
+
+    let frame = frames.next_frame().unwrap();
+    assert_eq!(frame[0], ramp(0..8));
+    assert_eq!(frame[1], ramp(0..8).iter().map(|sample| -sample).collect::<Vec<f32>>());
+    assert!(frames.next_frame().is_none(), "Only 6 samples are left");
+
+    frames.push(&counting(10..16), 2);
+    assert_eq!(frames.next_frame().unwrap()[0], ramp(4..12));
+    assert_eq!(frames.next_frame().unwrap()[0], ramp(8..16));
+    assert!(frames.next_frame().is_none());
+  }
+
+  #[test]
+  fn keeps_every_channel () {
+    let mut frames = FrameBuffer::create(2, 2);
+    frames.push(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3);
+    assert_eq!(frames.next_frame().unwrap(), vec![vec![1.0, 4.0], vec![2.0, 5.0], vec![3.0, 6.0]]);
+  }
+
+  #[test]
+  fn drops_the_samples_when_the_channels_change () {
+    let mut frames = FrameBuffer::create(4, 4);
+    frames.push(&[0.0; 6], 2);
+    frames.push(&[1.0; 2], 1);
+    assert!(frames.next_frame().is_none());
+    frames.push(&[1.0; 2], 1);
+    assert_eq!(frames.next_frame().unwrap(), vec![vec![1.0; 4]]);
+  }
+}
@@ -0,0 +1,285 @@
+// Synthetic signal generator -- known input to test the analysis and renderers
+use std::f32::consts::PI;
+use std::time::Instant;
+
+use super::source::{ChannelSource, Pacer, CHUNK_FRAMES};
+
+// The generator always produces stereo, and plays back at this rate
+pub const SAMPLE_RATE: u32 = 44_100;
+
+// Defaults for the optional parameters of the generator spec
+const DEFAULT_SWEEP_END: f32 = 20_000.0;
+const SWEEP_DURATION: f32 = 10.0; // in seconds
+const DEFAULT_BPM: f32 = 120.0;
+const CLICK_DURATION: f32 = 0.01; // in seconds
+
+#[derive(Clone, Copy, Debug)]
+pub enum Waveform {
+  Sine,
+  Square,
+  Sweep(f32), // Logarithmic sweep from the frequency to the given end frequency
+  WhiteNoise,
+  PinkNoise,
+  Click(f32) // Short clicks pitched at the frequency with the given BPM
+}
+
+/// One signal of the generator. Several of them are mixed together.
+#[derive(Clone, Debug)]
+pub struct Signal {
+  pub waveform: Waveform,
+  pub frequency: f32, // in Hz
+  pub amplitude: f32, // 0.0 to 1.0
+  pub pan: f32 // -1.0 (left) to 1.0 (right)
+}
+
+impl Signal {
+  pub fn create (waveform: Waveform, frequency: f32, amplitude: f32, pan: f32) -> Self {
+    Self { waveform, frequency, amplitude, pan }
+  }
+
+  /// Parses a signal spec of the form kind:frequency:amplitude:pan[:extra],
+  /// where kind is one of sine, square, sweep, white, pink or click, and
+  /// extra is the end frequency of a sweep or the BPM of a click track.
+  /// Sweeps must stay below the Nyquist frequency of the given sample rate,
+  /// which is the one the signal will be generated at.
+  pub fn parse (spec: &str, sample_rate: u32) -> Result<Self, String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    let number = |idx: usize, default: f32| -> Result<f32, String> {
+      match parts.get(idx) {
+        Some(part) => part.parse().map_err(|_| format!("Invalid number {} in signal {}", part, spec)),
+        None => Ok(default)
+      }
+    };
+
+    let waveform = match parts[0] {
+      "sine" => Waveform::Sine,
+      "square" => Waveform::Square,
+      "sweep" => Waveform::Sweep(number(4, DEFAULT_SWEEP_END)?),
+      "white" => Waveform::WhiteNoise,
+      "pink" => Waveform::PinkNoise,
+      "click" => Waveform::Click(number(4, DEFAULT_BPM)?),
+      kind => return Err(format!("Unknown signal {}", kind))
+    };
+
+    let frequency = number(1, 440.0)?;
+    if let Waveform::Sweep(end) = waveform {
+      // The sweep is exponential, so it can't start or end at 0 Hz
+      let nyquist = sample_rate as f32 / 2.0;
+      for bound in [frequency, end] {
+        if !(bound > 0.0 && bound < nyquist) {
+          return Err(format!("Sweep frequency {} in signal {} must be between 0 and {} Hz", bound, spec, nyquist));
+        }
+      }
+    }
+
+    Ok(Self::create(waveform, frequency, number(2, 0.5)?.clamp(0.0, 1.0), number(3, 0.0)?.clamp(-1.0, 1.0)))
+  }
+
+  /// Returns the gains for the left and right channel (constant power panning)
+  fn gains (&self) -> (f32, f32) {
+    let angle = (self.pan + 1.0) * PI / 4.0;
+    (self.amplitude * angle.cos(), self.amplitude * angle.sin())
+  }
+}
+
+/// Parses several signal specs joined by a +, e.g. sine:440:0.5:-1+sine:660:0.5:1
+pub fn parse_signals (spec: &str, sample_rate: u32) -> Result<Vec<Signal>, String> {
+  spec.split('+').map(|signal| Signal::parse(signal, sample_rate)).collect()
+}
+
+/// The signals that are always available as inputs
+pub fn fetch_presets () -> Vec<(String, Vec<Signal>)> {
+  vec![
+    (String::from("Generator: Sine 440 Hz"), vec![Signal::create(Waveform::Sine, 440.0, 0.5, 0.0)]),
+    (String::from("Generator: Sweep 20 Hz to 20 kHz"), vec![Signal::create(Waveform::Sweep(20_000.0), 20.0, 0.5, 0.0)]),
+    (String::from("Generator: Square 220 Hz"), vec![Signal::create(Waveform::Square, 220.0, 0.5, 0.0)]),
+    (String::from("Generator: White noise"), vec![Signal::create(Waveform::WhiteNoise, 0.0, 0.5, 0.0)]),
+    (String::from("Generator: Pink noise"), vec![Signal::create(Waveform::PinkNoise, 0.0, 0.5, 0.0)]),
+    (String::from("Generator: Click track 120 BPM"), vec![Signal::create(Waveform::Click(120.0), 1_000.0, 0.8, 0.0)]),
+    (String::from("Generator: Stereo 440 Hz L / 660 Hz R"), vec![
+      Signal::create(Waveform::Sine, 440.0, 0.5, -1.0),
+      Signal::create(Waveform::Sine, 660.0, 0.5, 1.0)
+    ])
+  ]
+}
+
+/// Synthesizes the signals. This is fully deterministic (even the noise),
+/// so the same signals always result in the same samples.
+pub struct Generator {
+  signals: Vec<Signal>,
+  sample_rate: u32,
+  frame: u64, // The number of frames generated so far
+  phases: Vec<f32>, // Current phase (0.0 to 1.0) per signal
+  noise_state: u32, // xorshift state
+  pink_state: Vec<[f32; 7]> // Filter state of the pink noise per signal
+}
+
+impl Generator {
+  pub fn create (signals: Vec<Signal>, sample_rate: u32) -> Self {
+    let count = signals.len();
+    Self {
+      signals,
+      sample_rate,
+      frame: 0,
+      phases: vec![0.0; count],
+      noise_state: 0x1234_5678,
+      pink_state: vec![[0.0; 7]; count]
+    }
+  }
+
+  pub fn sample_rate (&self) -> u32 {
+    self.sample_rate
+  }
+
+  /// Returns white noise between -1.0 and 1.0
+  fn next_noise (&mut self) -> f32 {
+    // xorshift32, good enough for audio and much cheaper than a real RNG
+    let mut x = self.noise_state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.noise_state = x;
+    x as f32 / u32::MAX as f32 * 2.0 - 1.0
+  }
+
+  /// Generates the next value of the signal at the given index
+  fn next_value (&mut self, idx: usize) -> f32 {
+    let signal = self.signals[idx].clone();
+    // f64, as f32 would lose precision after a few minutes' worth of frames
+    let time = self.frame as f64 / self.sample_rate as f64;
+
+    // The instantaneous frequency of the signal
+    let frequency = match signal.waveform {
+      Waveform::Sweep(end) => {
+        let progress = (time % SWEEP_DURATION as f64) as f32 / SWEEP_DURATION;
+        signal.frequency * (end / signal.frequency).powf(progress)
+      },
+      _ => signal.frequency
+    };
+
+    let phase = self.phases[idx];
+    self.phases[idx] = (phase + frequency / self.sample_rate as f32).fract();
+
+    match signal.waveform {
+      Waveform::Sine | Waveform::Sweep(_) => (phase * 2.0 * PI).sin(),
+      Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+      Waveform::WhiteNoise => self.next_noise(),
+      Waveform::PinkNoise => {
+        // Paul Kellet's refined pink noise filter
+        let white = self.next_noise();
+        let b = &mut self.pink_state[idx];
+        b[0] = 0.99886 * b[0] + white * 0.055_517_9;
+        b[1] = 0.99332 * b[1] + white * 0.075_075_9;
+        b[2] = 0.96900 * b[2] + white * 0.153_852;
+        b[3] = 0.86650 * b[3] + white * 0.310_485_6;
+        b[4] = 0.55000 * b[4] + white * 0.532_952_2;
+        b[5] = -0.7616 * b[5] - white * 0.016_898;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115_926;
+        pink * 0.11 // Roughly back to -1.0 to 1.0
+      },
+      Waveform::Click(bpm) => {
+        let since_click = (time % (60.0 / bpm as f64)) as f32;
+        if since_click < CLICK_DURATION {
+          // A short, decaying sine burst
+          (phase * 2.0 * PI).sin() * (1.0 - since_click / CLICK_DURATION)
+        } else {
+          0.0
+        }
+      }
+    }
+  }
+
+  /// Generates the next frames as interleaved stereo samples
+  pub fn next_chunk (&mut self, frames: usize) -> Vec<f32> {
+    let mut ret = Vec::with_capacity(frames * 2);
+    for _ in 0..frames {
+      let mut left = 0.0;
+      let mut right = 0.0;
+      for idx in 0..self.signals.len() {
+        let value = self.next_value(idx);
+        let (gain_left, gain_right) = self.signals[idx].gains();
+        left += value * gain_left;
+        right += value * gain_right;
+      }
+      ret.push(left.clamp(-1.0, 1.0));
+      ret.push(right.clamp(-1.0, 1.0));
+      self.frame += 1;
+    }
+    ret
+  }
+}
+
+/// Plays back the generated signals in real time
+pub fn create_generator_source (name: String, signals: Vec<Signal>) -> ChannelSource {
+  let mut generator = Generator::create(signals, SAMPLE_RATE);
+
+  ChannelSource::spawn(name, generator.sample_rate(), 2, move |tx, state| {
+    let mut pacer = Pacer::create(generator.sample_rate());
+
+    while !state.is_stopped() {
+      if !state.is_playing() {
+        pacer.idle();
+        continue;
+      }
+
+      pacer.wait();
+      if tx.send((generator.next_chunk(CHUNK_FRAMES), Instant::now())).is_err() {
+        return; // Nobody's listening anymore
+      }
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::testing::{mono, assert_close};
+
+  #[test]
+  fn parses_signal_specs () {
+    let signals = parse_signals("sine:440:0.5:-1+sweep:20:0.5:0:20000+click:1000", 44_100).unwrap();
+    assert_eq!(signals.len(), 3);
+    assert!(matches!(signals[0].waveform, Waveform::Sine));
+    assert_eq!((signals[0].frequency, signals[0].amplitude, signals[0].pan), (440.0, 0.5, -1.0));
+    assert!(matches!(signals[1].waveform, Waveform::Sweep(end) if end == 20_000.0));
+    assert!(matches!(signals[2].waveform, Waveform::Click(bpm) if bpm == DEFAULT_BPM));
+
+    assert!(Signal::parse("saw:440", 44_100).is_err());
+    assert!(Signal::parse("sine:loud", 44_100).is_err());
+  }
+
+  #[test]
+  fn rejects_sweeps_beyond_the_nyquist_frequency_of_the_sample_rate () {
+    assert!(Signal::parse("sweep:0:0.5:0:1000", 44_100).is_err());
+    assert!(Signal::parse("sweep:20:0.5:0:0", 44_100).is_err());
+    assert!(Signal::parse("sweep:-20:0.5:0", 44_100).is_err());
+    assert!(Signal::parse("sweep:20000:0.5:0:20", 44_100).is_ok());
+
+    // 22.5 kHz is beyond the Nyquist frequency of 44.1 kHz, but not of 48 kHz
+    assert!(Signal::parse("sweep:20:0.5:0:22500", 44_100).is_err());
+    assert!(Signal::parse("sweep:20:0.5:0:22500", 48_000).is_ok());
+    assert!(Signal::parse("sweep:20:0.5:0:24000", 48_000).is_err());
+  }
+
+  #[test]
+  fn generates_the_same_samples_every_time () {
+    let signals = parse_signals("pink:0:0.5:-1+sine:1000:0.5:1", 48_000).unwrap();
+    let samples = Generator::create(signals.clone(), 48_000).next_chunk(1_000);
+    assert_eq!(samples.len(), 2_000);
+    assert_eq!(samples, Generator::create(signals, 48_000).next_chunk(1_000));
+  }
+
+  #[test]
+  fn pans_with_constant_power () {
+    // Hard left puts the full amplitude on the left channel
+    let left = mono(Waveform::Square, 100.0, 0.5, 48_000, 1_000);
+    assert!(left.iter().all(|sample| sample.abs() == 0.5));
+
+    let stereo = Generator::create(vec![Signal::create(Waveform::Square, 100.0, 0.5, 0.0)], 48_000).next_chunk(1_000);
+    for frame in stereo.chunks_exact(2) {
+      assert_close(frame[0].abs(), 0.5 / 2_f32.sqrt(), 1e-6);
+      assert_eq!(frame[0], frame[1]);
+    }
+  }
+}
@@ -0,0 +1,100 @@
+// Hot-plug -- watches input devices being plugged in and out
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::HostId;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time;
+
+use super::util::{fetch_devices, AudioDevice};
+
+// How often the host is asked for its devices
+const POLL_INTERVAL: time::Duration = time::Duration::from_secs(2);
+
+/// What the watcher sends whenever the devices have changed
+pub struct DeviceList {
+  pub devices: Vec<AudioDevice>, // The complete device list, see fetch_devices
+  pub present: Vec<String> // The names of all devices of the host. Unlike the list above, these include devices that are busy, e.g. because we listen to them.
+}
+
+/// Polls the devices of a host on a thread of its own, since listing them
+/// can take a while. Whenever they change, it sends over the complete
+/// device list. The first list is always sent.
+pub struct DeviceWatcher {
+  receiver: mpsc::Receiver<DeviceList>,
+  stopped: Arc<AtomicBool> // Tells the thread to wind down
+}
+
+impl DeviceWatcher {
+  pub fn create (host: HostId) -> Self {
+    let (tx, rx) = mpsc::channel();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread_stopped = stopped.clone();
+
+    thread::spawn(move || {
+      // Only the names are polled, the full list (which queries the
+      // configurations of every device) is fetched once they change
+      let mut last_names: Option<Vec<String>> = None;
+      while !thread_stopped.load(Ordering::SeqCst) {
+        // If the host can't tell us right now, we ask again next time
+        if let Some(names) = device_names(host) {
+          if last_names.as_ref() != Some(&names) {
+            match fetch_devices(host) {
+              Ok(devices) => {
+                if tx.send(DeviceList { devices, present: names.clone() }).is_err() {
+                  break; // Nobody's watching anymore
+                }
+              },
+              Err(e) => println!("ERROR: {}", e)
+            }
+            last_names = Some(names);
+          }
+        }
+        thread::sleep(POLL_INTERVAL);
+      }
+    });
+
+    Self {
+      receiver: rx,
+      stopped
+    }
+  }
+
+  /// Returns the newest device list, if the devices changed since the last call
+  pub fn poll (&self) -> Option<DeviceList> {
+    self.receiver.try_iter().last()
+  }
+}
+
+impl Drop for DeviceWatcher {
+  fn drop (&mut self) {
+    self.stopped.store(true, Ordering::SeqCst);
+  }
+}
+
+/// The names of all devices of the host, or none if it can't list them. Not
+/// all of them need to be input devices: Telling these apart means querying
+/// their configurations, which is what makes input_devices so slow.
+fn device_names (host: HostId) -> Option<Vec<String>> {
+  #[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+  {
+    if host == HostId::Alsa {
+      return alsa_device_names();
+    }
+  }
+
+  let devices = cpal::host_from_id(host).ok()?.devices().ok()?;
+  Some(devices.map(|device| device.name().unwrap_or_default()).collect())
+}
+
+/// On ALSA, even listing cpal's devices opens every PCM in both directions,
+/// whereas the hints they are made from only name them
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
+fn alsa_device_names () -> Option<Vec<String>> {
+  let hints = alsa::device_name::HintIter::new_str(None, "pcm").ok()?;
+  Some(hints
+    .filter(|hint| hint.direction != Some(alsa::Direction::Playback))
+    .filter_map(|hint| hint.name)
+    .collect())
+}
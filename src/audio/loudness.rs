@@ -0,0 +1,410 @@
+// Loudness metering -- RMS, sample and true peaks, and EBU R128 loudness of the raw stream
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// All measurements are based on blocks of 100 ms
+const BLOCKS_PER_SECOND: u32 = 10;
+// The windows, in blocks
+const RMS_BLOCKS: usize = 3; // 300 ms
+const MOMENTARY_BLOCKS: usize = 4; // 400 ms
+const SHORT_TERM_BLOCKS: usize = 30; // 3 s
+// Gating as per EBU R128 and EBU Tech 3342 (loudness range)
+const ABSOLUTE_GATE: f64 = -70.0; // LUFS
+const RELATIVE_GATE: f64 = -10.0; // LU below the ungated integrated loudness
+const RANGE_RELATIVE_GATE: f64 = -20.0; // LU below the ungated short-term loudness
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+// The windows since the start are kept in histograms from the absolute gate
+// up to +5 LUFS, in steps of 0.1 LU (like libebur128 does)
+const HISTOGRAM_MAX: f64 = 5.0; // LUFS
+const HISTOGRAM_RESOLUTION: f64 = 0.1; // LU per bin
+// The integrated loudness and loudness range change slowly, so we only
+// recalculate them every second
+const INTEGRATION_INTERVAL: usize = 10; // blocks
+// True peaks are found by upsampling 4x, with 12 taps per phase
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+/// The measurements, published after each block. Levels are in dBFS,
+/// loudness in LUFS, and everything is negative infinity for silence.
+#[derive(Clone, Debug)]
+pub struct Loudness {
+  pub rms: Vec<f32>, // Per channel, over the last 300 ms
+  pub sample_peak: Vec<f32>, // Per channel, within the last block
+  pub true_peak: Vec<f32>, // Per channel, within the last block (in dBTP)
+  pub max_true_peak: f32, // The highest true peak of all channels since the start (in dBTP)
+  pub momentary: f32, // Over the last 400 ms
+  pub short_term: f32, // Over the last 3 s
+  pub integrated: f32, // Since the start, gated
+  pub loudness_range: f32 // In LU, since the start
+}
+
+impl Loudness {
+  pub fn create (channels: usize) -> Self {
+    Self {
+      rms: vec![f32::NEG_INFINITY; channels],
+      sample_peak: vec![f32::NEG_INFINITY; channels],
+      true_peak: vec![f32::NEG_INFINITY; channels],
+      max_true_peak: f32::NEG_INFINITY,
+      momentary: f32::NEG_INFINITY,
+      short_term: f32::NEG_INFINITY,
+      integrated: f32::NEG_INFINITY,
+      loudness_range: 0.0
+    }
+  }
+}
+
+/// A biquad in direct form I
+struct Biquad {
+  b: [f64; 3],
+  a: [f64; 3], // a[0] is always 1.0
+  x: [f64; 2],
+  y: [f64; 2]
+}
+
+impl Biquad {
+  fn process (&mut self, sample: f64) -> f64 {
+    let output = self.b[0] * sample + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+      - self.a[1] * self.y[0] - self.a[2] * self.y[1];
+    self.x = [sample, self.x[0]];
+    self.y = [output, self.y[0]];
+    output
+  }
+}
+
+/// Creates the two stages of the K-weighting filter (ITU-R BS.1770) for any
+/// sample rate: a high shelf that models the head, and a high pass.
+fn k_weighting (sample_rate: f64) -> [Biquad; 2] {
+  let frequency = 1_681.974_450_955_533;
+  let gain = 3.999_843_853_973_347;
+  let q = 0.707_175_236_955_419_6;
+  let k = (PI * frequency / sample_rate).tan();
+  let vh = 10_f64.powf(gain / 20.0);
+  let vb = vh.powf(0.499_666_774_154_541_6);
+  let a0 = 1.0 + k / q + k * k;
+  let shelf = Biquad {
+    b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+    a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    x: [0.0; 2],
+    y: [0.0; 2]
+  };
+
+  let frequency = 38.135_470_876_024_44;
+  let q = 0.500_327_037_323_877_3;
+  let k = (PI * frequency / sample_rate).tan();
+  let a0 = 1.0 + k / q + k * k;
+  let high_pass = Biquad {
+    b: [1.0, -2.0, 1.0],
+    a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    x: [0.0; 2],
+    y: [0.0; 2]
+  };
+
+  [shelf, high_pass]
+}
+
+/// Calculates the coefficients of the interpolation filter for the true
+/// peaks: a windowed sinc with its cutoff at the original Nyquist frequency,
+/// split up into one set of taps per phase
+fn oversampling_coefficients () -> [[f64; TAPS_PER_PHASE]; OVERSAMPLING] {
+  let length = OVERSAMPLING * TAPS_PER_PHASE;
+  let center = (length - 1) as f64 / 2.0;
+  let mut phases = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+  for i in 0..length {
+    let x = (i as f64 - center) / OVERSAMPLING as f64;
+    let sinc = if x.abs() < 1e-9 { 1.0 } else { (PI * x).sin() / (PI * x) };
+    let window = 0.5 - 0.5 * (2.0 * PI * (i as f64 + 0.5) / length as f64).cos(); // Hann
+    phases[i % OVERSAMPLING][i / OVERSAMPLING] = sinc * window;
+  }
+  phases
+}
+
+/// The sums of one block (or window) per channel
+#[derive(Clone)]
+struct Block {
+  weighted: Vec<f64>, // Mean square of the K-weighted samples
+  squares: Vec<f64> // Mean square of the samples
+}
+
+/// Counts the windows since the start by their loudness. Its size is fixed,
+/// so measuring for hours takes neither more memory nor more time.
+struct LoudnessHistogram {
+  counts: Vec<u64>, // How many windows fell into each bin
+  powers: Vec<f64> // Their summed power, so the mean of whole bins is exact
+}
+
+impl LoudnessHistogram {
+  fn create () -> Self {
+    let bins = ((HISTOGRAM_MAX - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION).round() as usize;
+    Self {
+      counts: vec![0; bins],
+      powers: vec![0.0; bins]
+    }
+  }
+
+  /// The bin of a loudness. Anything louder than the histogram goes into the
+  /// highest bin, anything quieter into the lowest one.
+  fn bin (&self, lufs: f64) -> usize {
+    (((lufs - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION).max(0.0) as usize).min(self.counts.len() - 1)
+  }
+
+  fn add (&mut self, power: f64) {
+    let bin = self.bin(to_lufs(power));
+    self.counts[bin] += 1;
+    self.powers[bin] += power;
+  }
+
+  /// The mean power of all windows from the bin of the given loudness up,
+  /// or none if there aren't any. The whole bin holding the given loudness
+  /// counts, so windows up to one bin (0.1 LU) below it are included: Gating
+  /// with this is accurate to a bin, not exact.
+  fn mean_power (&self, from: f64) -> Option<f64> {
+    let first = self.bin(from);
+    let count: u64 = self.counts[first..].iter().sum();
+    if count == 0 {
+      return None;
+    }
+    Some(self.powers[first..].iter().sum::<f64>() / count as f64)
+  }
+
+  /// The loudness (the center of the bin) below which the given fraction of
+  /// the windows from the bin of the given loudness up lie
+  fn percentile (&self, from: f64, fraction: f64) -> f64 {
+    let first = self.bin(from);
+    let count: u64 = self.counts[first..].iter().sum();
+    let target = (count.saturating_sub(1) as f64 * fraction).round() as u64;
+    let mut seen = 0;
+    for (bin, bin_count) in self.counts.iter().enumerate().skip(first) {
+      seen += bin_count;
+      if seen > target {
+        return ABSOLUTE_GATE + (bin as f64 + 0.5) * HISTOGRAM_RESOLUTION;
+      }
+    }
+    f64::NEG_INFINITY // Empty
+  }
+}
+
+/// Measures the levels and the loudness of an interleaved stream
+pub struct LoudnessMeter {
+  channels: usize,
+  filters: Vec<[Biquad; 2]>, // K-weighting per channel
+  oversampling: [[f64; TAPS_PER_PHASE]; OVERSAMPLING],
+  history: Vec<[f64; TAPS_PER_PHASE]>, // The last samples per channel (newest first), for the oversampling
+  block_size: usize, // in frames
+  block_position: usize,
+  current: Block, // The sums of the block that is still running
+  current_peaks: Vec<f32>,
+  current_true_peaks: Vec<f32>,
+  blocks: VecDeque<Block>, // The last finished blocks
+  gating_blocks: LoudnessHistogram, // Every 400 ms window above the absolute gate
+  short_term_windows: LoudnessHistogram, // Every 3 s window above the absolute gate
+  since_integration: usize, // in blocks
+  loudness: Loudness
+}
+
+impl LoudnessMeter {
+  pub fn create (sample_rate: u32, channels: usize) -> Self {
+    let channels = channels.max(1);
+    Self {
+      channels,
+      filters: (0..channels).map(|_| k_weighting(sample_rate as f64)).collect(),
+      oversampling: oversampling_coefficients(),
+      history: vec![[0.0; TAPS_PER_PHASE]; channels],
+      block_size: (sample_rate / BLOCKS_PER_SECOND).max(1) as usize,
+      block_position: 0,
+      current: Block { weighted: vec![0.0; channels], squares: vec![0.0; channels] },
+      current_peaks: vec![0.0; channels],
+      current_true_peaks: vec![0.0; channels],
+      blocks: VecDeque::new(),
+      gating_blocks: LoudnessHistogram::create(),
+      short_term_windows: LoudnessHistogram::create(),
+      since_integration: 0,
+      loudness: Loudness::create(channels)
+    }
+  }
+
+  /// Returns the latest measurements
+  pub fn loudness (&self) -> &Loudness {
+    &self.loudness
+  }
+
+  /// Measures an interleaved buffer
+  pub fn process (&mut self, buf: &[f32]) {
+    for frame in buf.chunks_exact(self.channels) {
+      for (channel, sample) in frame.iter().enumerate() {
+        let sample_f64 = *sample as f64;
+
+        let weighted = self.filters[channel].iter_mut().fold(sample_f64, |value, filter| filter.process(value));
+        self.current.weighted[channel] += weighted * weighted;
+        self.current.squares[channel] += sample_f64 * sample_f64;
+        self.current_peaks[channel] = self.current_peaks[channel].max(sample.abs());
+
+        let true_peak = self.oversample(channel, sample_f64);
+        self.current_true_peaks[channel] = self.current_true_peaks[channel].max(true_peak);
+      }
+
+      self.block_position += 1;
+      if self.block_position == self.block_size {
+        self.finish_block();
+      }
+    }
+  }
+
+  /// Feeds a sample into the interpolation filter of a channel, and returns
+  /// the highest magnitude between this sample and the previous one
+  fn oversample (&mut self, channel: usize, sample: f64) -> f32 {
+    let history = &mut self.history[channel];
+    history.copy_within(0..TAPS_PER_PHASE - 1, 1);
+    history[0] = sample;
+
+    let mut peak = sample.abs();
+    for coefficients in self.oversampling.iter() {
+      let value: f64 = history.iter().zip(coefficients.iter()).map(|(value, coefficient)| value * coefficient).sum();
+      peak = peak.max(value.abs());
+    }
+    peak as f32
+  }
+
+  fn finish_block (&mut self) {
+    let size = self.block_size as f64;
+    let block = Block {
+      weighted: self.current.weighted.iter().map(|sum| sum / size).collect(),
+      squares: self.current.squares.iter().map(|sum| sum / size).collect()
+    };
+
+    self.blocks.push_front(block);
+    self.blocks.truncate(SHORT_TERM_BLOCKS);
+
+    self.loudness.rms = (0..self.channels).map(|channel| {
+      let blocks = self.blocks.len().min(RMS_BLOCKS);
+      let mean: f64 = self.blocks.iter().take(blocks).map(|block| block.squares[channel]).sum::<f64>() / blocks as f64;
+      (10.0 * mean.log10()) as f32
+    }).collect();
+    self.loudness.sample_peak = self.current_peaks.iter().map(|peak| 20.0 * peak.log10()).collect();
+    self.loudness.true_peak = self.current_true_peaks.iter().map(|peak| 20.0 * peak.log10()).collect();
+    for true_peak in self.loudness.true_peak.iter() {
+      self.loudness.max_true_peak = self.loudness.max_true_peak.max(*true_peak);
+    }
+
+    // The momentary loudness is measured every 100 ms, which at the same
+    // time gives us the overlapping 400 ms blocks for the gating
+    if self.blocks.len() >= MOMENTARY_BLOCKS {
+      let power = self.window_power(MOMENTARY_BLOCKS);
+      self.loudness.momentary = to_lufs(power) as f32;
+      if to_lufs(power) > ABSOLUTE_GATE {
+        self.gating_blocks.add(power);
+      }
+    }
+
+    if self.blocks.len() >= SHORT_TERM_BLOCKS {
+      let power = self.window_power(SHORT_TERM_BLOCKS);
+      self.loudness.short_term = to_lufs(power) as f32;
+      if to_lufs(power) > ABSOLUTE_GATE {
+        self.short_term_windows.add(power);
+      }
+    }
+
+    self.since_integration += 1;
+    if self.since_integration >= INTEGRATION_INTERVAL {
+      self.since_integration = 0;
+      self.loudness.integrated = self.integrated() as f32;
+      self.loudness.loudness_range = self.loudness_range() as f32;
+    }
+
+    // Start the next block
+    self.block_position = 0;
+    self.current = Block { weighted: vec![0.0; self.channels], squares: vec![0.0; self.channels] };
+    self.current_peaks = vec![0.0; self.channels];
+    self.current_true_peaks = vec![0.0; self.channels];
+  }
+
+  /// The mean square of the K-weighted samples over the last blocks, summed
+  /// over all channels (all of them have a weight of 1.0 for mono and stereo)
+  fn window_power (&self, blocks: usize) -> f64 {
+    (0..self.channels).map(|channel| {
+      self.blocks.iter().take(blocks).map(|block| block.weighted[channel]).sum::<f64>() / blocks as f64
+    }).sum()
+  }
+
+  /// The mean of the gating blocks, gated once more relative to itself. The
+  /// relative gate is applied to whole bins of the histogram, see mean_power.
+  fn integrated (&self) -> f64 {
+    let ungated = match self.gating_blocks.mean_power(ABSOLUTE_GATE) {
+      Some(power) => power,
+      None => return f64::NEG_INFINITY
+    };
+
+    let threshold = to_lufs(ungated) + RELATIVE_GATE;
+    self.gating_blocks.mean_power(threshold).map_or(f64::NEG_INFINITY, to_lufs)
+  }
+
+  /// The spread between soft and loud passages (EBU Tech 3342)
+  fn loudness_range (&self) -> f64 {
+    let ungated = match self.short_term_windows.mean_power(ABSOLUTE_GATE) {
+      Some(power) => power,
+      None => return 0.0
+    };
+
+    let threshold = to_lufs(ungated) + RANGE_RELATIVE_GATE;
+    self.short_term_windows.percentile(threshold, RANGE_HIGH_PERCENTILE)
+      - self.short_term_windows.percentile(threshold, RANGE_LOW_PERCENTILE)
+  }
+}
+
+/// Converts a (K-weighted) power into LUFS
+fn to_lufs (power: f64) -> f64 {
+  -0.691 + 10.0 * power.log10()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::generator::Waveform;
+  use super::super::testing::{stereo, assert_close};
+
+  // A 1 kHz sine with a peak level of the given dBFS on both channels
+  fn reference_tone (level: f32, seconds: usize) -> Vec<f32> {
+    stereo(Waveform::Sine, 1_000.0, 10_f32.powf(level / 20.0), 48_000, 48_000 * seconds)
+  }
+
+  #[test]
+  fn measures_a_reference_tone () {
+    // As per EBU Tech 3341, a stereo 1 kHz sine at -23 dBFS reads -23 LUFS
+    let mut meter = LoudnessMeter::create(48_000, 2);
+    meter.process(&reference_tone(-23.0, 10));
+    let loudness = meter.loudness();
+
+    assert_close(loudness.momentary, -23.0, 0.1);
+    assert_close(loudness.short_term, -23.0, 0.1);
+    assert_close(loudness.integrated, -23.0, 0.1);
+    assert_close(loudness.loudness_range, 0.0, 0.1);
+    for channel in 0..2 {
+      assert_close(loudness.sample_peak[channel], -23.0, 0.1);
+      assert_close(loudness.true_peak[channel], -23.0, 0.1);
+      assert_close(loudness.rms[channel], -26.0, 0.1);
+    }
+  }
+
+  #[test]
+  fn measures_the_loudness_range () {
+    // Two tones 10 LU apart, each long enough to dominate its percentile
+    let mut meter = LoudnessMeter::create(48_000, 2);
+    meter.process(&reference_tone(-30.0, 10));
+    meter.process(&reference_tone(-20.0, 10));
+    let loudness = meter.loudness();
+
+    assert_close(loudness.loudness_range, 10.0, 0.2);
+    // The quiet part is within 10 LU of the loud one, so nothing is gated
+    assert_close(loudness.integrated, -22.6, 0.1);
+  }
+
+  #[test]
+  fn measures_silence_as_negative_infinity () {
+    let mut meter = LoudnessMeter::create(48_000, 2);
+    meter.process(&[0.0; 48_000 * 2 * 5]);
+    let loudness = meter.loudness();
+    assert_eq!(loudness.momentary, f32::NEG_INFINITY);
+    assert_eq!(loudness.integrated, f32::NEG_INFINITY);
+    assert_eq!(loudness.loudness_range, 0.0);
+  }
+}
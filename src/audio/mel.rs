@@ -0,0 +1,107 @@
+// Mel filterbank -- groups the spectrum into bands that are equally spaced in pitch as we perceive it
+
+/// The band counts offered in the UI
+pub const MEL_BAND_COUNTS: [usize; 4] = [24, 40, 64, 128];
+pub const DEFAULT_MEL_BAND_COUNT: usize = 40;
+
+// The filterbank covers the audible range (or up to the Nyquist frequency)
+const MIN_FREQUENCY: f32 = 20.0;
+const MAX_FREQUENCY: f32 = 20_000.0;
+
+/// Converts a frequency in Hz to mel (the HTK formula)
+fn to_mel (frequency: f32) -> f32 {
+  2595.0 * (1.0 + frequency / 700.0).log10()
+}
+
+/// Converts mel back to a frequency in Hz
+fn from_mel (mel: f32) -> f32 {
+  700.0 * (10_f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// A set of overlapping triangular filters, spaced evenly on the mel scale.
+/// The weights depend on the bin frequency, so the filterbank needs to be
+/// recreated whenever the FFT length or the sample rate change.
+pub struct MelFilterbank {
+  filters: Vec<Vec<(usize, f32)>>, // For each band the bins it covers, and their weights
+  frequencies: Vec<f32>, // The center frequency of each band
+  bin_frequency: f32,
+  bin_count: usize
+}
+
+impl MelFilterbank {
+  pub fn create (band_count: usize, bin_frequency: f32, bin_count: usize) -> Self {
+    let nyquist = bin_frequency * bin_count.saturating_sub(1) as f32;
+    let min_mel = to_mel(MIN_FREQUENCY);
+    let max_mel = to_mel(MAX_FREQUENCY.min(nyquist).max(MIN_FREQUENCY));
+
+    // Each filter starts at the center of the previous one and ends at the
+    // center of the next one, so we need two more points than bands
+    let points: Vec<f32> = (0..band_count + 2).map(|i| {
+      from_mel(min_mel + (max_mel - min_mel) * i as f32 / (band_count + 1) as f32)
+    }).collect();
+
+    let mut filters = Vec::new();
+    for band in 0..band_count {
+      let (lower, center, upper) = (points[band], points[band + 1], points[band + 2]);
+
+      let mut weights = Vec::new();
+      let first = (lower / bin_frequency).ceil() as usize;
+      let last = ((upper / bin_frequency).floor() as usize).min(bin_count.saturating_sub(1));
+      for bin in first..=last {
+        let frequency = bin as f32 * bin_frequency;
+        let weight = if frequency <= center {
+          (frequency - lower) / (center - lower)
+        } else {
+          (upper - frequency) / (upper - center)
+        };
+        if weight > 0.0 {
+          weights.push((bin, weight));
+        }
+      }
+
+      // In the bass, filters can be narrower than the bin spacing. These get
+      // the spectrum interpolated at their center instead.
+      if weights.is_empty() {
+        let position = center / bin_frequency;
+        let bin = position.floor() as usize;
+        if bin + 1 < bin_count {
+          let fraction = position - bin as f32;
+          weights.push((bin, 1.0 - fraction));
+          weights.push((bin + 1, fraction));
+        }
+      }
+
+      filters.push(weights);
+    }
+
+    Self {
+      filters,
+      frequencies: points[1..=band_count].to_vec(),
+      bin_frequency,
+      bin_count
+    }
+  }
+
+  /// Returns true if the filterbank was made for these parameters
+  pub fn matches (&self, band_count: usize, bin_frequency: f32, bin_count: usize) -> bool {
+    self.filters.len() == band_count && self.bin_frequency == bin_frequency && self.bin_count == bin_count
+  }
+
+  /// The center frequency of each band
+  pub fn frequencies (&self) -> &[f32] {
+    &self.frequencies
+  }
+
+  /// Applies the filters to a magnitude spectrum. Each band gets the root of
+  /// the weighted energy of its bins, so the result stays on the same scale
+  /// as the spectrum.
+  pub fn apply (&self, spectrum: &[f32]) -> Vec<f32> {
+    self.filters.iter().map(|weights| {
+      let energy: f32 = weights.iter()
+        .filter(|(bin, _weight)| *bin < spectrum.len())
+        .map(|(bin, weight)| weight * spectrum[*bin] * spectrum[*bin])
+        .sum();
+      energy.sqrt()
+    }).collect()
+  }
+}
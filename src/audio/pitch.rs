@@ -0,0 +1,140 @@
+// Pitch detection -- estimates the fundamental frequency with the YIN algorithm
+use super::decibel::DecibelScale;
+use super::chroma::{midi_note, NOTE_NAMES};
+
+// The range of fundamentals we look for, from the low E of a bass to well
+// above the highest note of a piccolo
+const MIN_FREQUENCY: f32 = 40.0;
+const MAX_FREQUENCY: f32 = 4_200.0;
+// The first dip of the normalized difference below this counts as the period
+const THRESHOLD: f32 = 0.15;
+// Quieter frames are not analyzed at all, since noise has no pitch
+const SILENCE: f32 = -60.0; // dBFS
+
+/// A detected pitch, including the closest note in equal temperament
+#[derive(Clone, Copy, Debug)]
+pub struct Pitch {
+  pub frequency: f32, // The fundamental frequency in Hz
+  pub clarity: f32, // How periodic the signal is, 0.0 to 1.0
+  pub note: &'static str, // The name of the closest note, e.g. "C#"
+  pub octave: i32, // The octave in scientific pitch notation, e.g. 4 for A4
+  pub cents: f32 // The deviation from the closest note, -50.0 to 50.0
+}
+
+impl Pitch {
+  /// Creates the pitch, with the notes derived from the given frequency of A4
+  pub fn create (frequency: f32, clarity: f32, reference_tuning: f32) -> Self {
+    let midi_note = midi_note(frequency, reference_tuning);
+    let closest = midi_note.round();
+    let note_number = closest as i32;
+
+    Self {
+      frequency,
+      clarity,
+      note: NOTE_NAMES[note_number.rem_euclid(12) as usize],
+      octave: note_number.div_euclid(12) - 1,
+      cents: (midi_note - closest) * 100.0
+    }
+  }
+}
+
+/// Estimates the pitch of a (mono) frame of samples. The frame needs to
+/// span at least two periods, so the lowest detectable pitch depends on the
+/// frame size. Returns None for silence or if there is no clear pitch.
+pub fn detect_pitch (samples: &[f32], sample_rate: u32, reference_tuning: f32) -> Option<Pitch> {
+  let sample_rate = sample_rate as f32;
+  let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len().max(1) as f32).sqrt();
+  if DecibelScale::to_decibels(rms) < SILENCE {
+    return None;
+  }
+
+  // The difference function compares the frame with itself shifted by tau.
+  // We don't need more samples than twice the longest period, and every
+  // additional one costs a lot, so we only use the most recent ones.
+  let max_tau = ((sample_rate / MIN_FREQUENCY).ceil() as usize).min(samples.len() / 2);
+  let min_tau = ((sample_rate / MAX_FREQUENCY).floor() as usize).max(2);
+  if min_tau + 2 >= max_tau {
+    return None; // The frame is too short
+  }
+  let samples = &samples[samples.len() - 2 * max_tau..];
+  let width = samples.len() - max_tau;
+
+  // Step 1 and 2: The difference function, cumulatively normalized by its
+  // mean, so it starts at 1.0 and dips towards 0.0 at every period
+  let mut normalized = vec![1.0; max_tau + 1];
+  let mut running_sum = 0.0;
+  for tau in 1..=max_tau {
+    let difference: f32 = samples[..width].iter().zip(samples[tau..tau + width].iter())
+      .map(|(a, b)| (a - b) * (a - b))
+      .sum();
+    running_sum += difference;
+    normalized[tau] = if running_sum > 0.0 { difference * tau as f32 / running_sum } else { 1.0 };
+  }
+
+  // Step 3: Take the first dip below the threshold (to avoid octave errors),
+  // and follow it down to its minimum
+  let mut tau = min_tau;
+  while tau < max_tau && normalized[tau] >= THRESHOLD {
+    tau += 1;
+  }
+  if tau >= max_tau {
+    return None; // Not periodic enough
+  }
+  while tau + 1 < max_tau && normalized[tau + 1] < normalized[tau] {
+    tau += 1;
+  }
+
+  // Step 4: Interpolate the minimum with a parabola for sub-sample accuracy
+  let (left, center, right) = (normalized[tau - 1], normalized[tau], normalized[tau + 1]);
+  let denominator = left - 2.0 * center + right;
+  let offset = if denominator.abs() > f32::EPSILON { 0.5 * (left - right) / denominator } else { 0.0 };
+  let period = tau as f32 + offset.clamp(-0.5, 0.5);
+
+  Some(Pitch::create(sample_rate / period, (1.0 - center).clamp(0.0, 1.0), reference_tuning))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::generator::Waveform;
+  use super::super::testing::{mono, assert_close};
+
+  #[test]
+  fn detects_the_pitch_of_sines () {
+    for frequency in [55.0, 110.0, 440.0, 1_000.0, 3_520.0] {
+      let pitch = detect_pitch(&mono(Waveform::Sine, frequency, 0.5, 44_100, 2_048), 44_100, 440.0).expect("No pitch detected");
+      assert_close(pitch.frequency, frequency, frequency * 0.002);
+      assert!(pitch.clarity > 0.9);
+    }
+
+    let pitch = detect_pitch(&mono(Waveform::Sine, 440.0, 0.5, 44_100, 2_048), 44_100, 440.0).unwrap();
+    assert_eq!((pitch.note, pitch.octave), ("A", 4));
+    assert_close(pitch.cents, 0.0, 1.0);
+  }
+
+  #[test]
+  fn ignores_the_harmonics () {
+    let pitch = detect_pitch(&mono(Waveform::Square, 220.0, 0.5, 44_100, 2_048), 44_100, 440.0).expect("No pitch detected");
+    assert_close(pitch.frequency, 220.0, 1.0);
+  }
+
+  #[test]
+  fn finds_no_pitch_in_silence_or_noise () {
+    assert!(detect_pitch(&[0.0; 2_048], 44_100, 440.0).is_none());
+    assert!(detect_pitch(&mono(Waveform::WhiteNoise, 0.0, 0.5, 44_100, 2_048), 44_100, 440.0).is_none());
+  }
+
+  #[test]
+  fn names_notes_relative_to_the_reference_tuning () {
+    let pitch = Pitch::create(261.63, 1.0, 440.0);
+    assert_eq!((pitch.note, pitch.octave), ("C", 4));
+    assert_close(pitch.cents, 0.0, 0.1);
+    let pitch = Pitch::create(450.0, 1.0, 440.0);
+    assert_eq!((pitch.note, pitch.octave), ("A", 4));
+    assert_close(pitch.cents, 38.9, 0.1);
+    // The same frequency is in tune with A4 at 450 Hz
+    let pitch = Pitch::create(450.0, 1.0, 450.0);
+    assert_eq!((pitch.note, pitch.octave), ("A", 4));
+    assert_close(pitch.cents, 0.0, 0.1);
+  }
+}
@@ -0,0 +1,133 @@
+// Raw PCM sources -- interleaved little endian f32 samples from stdin or the network
+use std::io::{ErrorKind, Read};
+use std::net::UdpSocket;
+use std::sync::{mpsc, Mutex, Once};
+use std::thread;
+use std::time;
+
+use super::source::{ChannelSource, CHUNK_FRAMES};
+
+// How many bytes the stdin reader takes at most at a time
+const STDIN_READ_SIZE: usize = 16_384;
+
+// What the stdin reader hands out, and when it read it
+type StdinBytes = (Vec<u8>, time::Instant);
+
+// Stdin is read by a single thread for the whole run, since a read blocks
+// until more input arrives. It hands what it reads to the stdin source that
+// was created last, and to nobody once that's gone.
+static STDIN_READER: Once = Once::new();
+static STDIN_LISTENER: Mutex<Option<mpsc::Sender<StdinBytes>>> = Mutex::new(None);
+
+/// Converts little endian bytes into samples, ignoring any incomplete trailing sample
+fn bytes_to_samples (bytes: &[u8]) -> Vec<f32> {
+  bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+/// Reads raw samples from stdin, e.g. `sox track.flac -t f32 - | visualizrs --stdin`
+pub fn create_stdin_source (sample_rate: u32, channels: usize) -> ChannelSource {
+  println!("Reading raw samples from stdin ...");
+
+  let (listener, bytes) = mpsc::channel();
+  *STDIN_LISTENER.lock().unwrap() = Some(listener); // Replaces the previous stdin source
+  STDIN_READER.call_once(|| {
+    thread::spawn(read_stdin);
+  });
+
+  ChannelSource::spawn(String::from("Stdin"), sample_rate, channels, move |tx, state| {
+    let chunk_len = CHUNK_FRAMES * channels * 4; // in bytes
+    let mut pending: Vec<u8> = Vec::new();
+
+    // The pipe sets the pace, so we simply forward whatever comes in
+    while !state.is_stopped() {
+      let (read, captured_at) = match bytes.recv_timeout(time::Duration::from_millis(100)) {
+        Ok(read) => read,
+        Err(mpsc::RecvTimeoutError::Timeout) => continue, // Check whether we should stop
+        Err(mpsc::RecvTimeoutError::Disconnected) => return // Another stdin source took over, or stdin has been closed
+      };
+
+      if !state.is_playing() {
+        pending.clear();
+        continue;
+      }
+
+      pending.extend(read);
+      while pending.len() >= chunk_len {
+        let chunk: Vec<u8> = pending.drain(0..chunk_len).collect();
+        if tx.send((bytes_to_samples(&chunk), captured_at)).is_err() {
+          return; // Nobody's listening anymore
+        }
+      }
+    }
+  })
+}
+
+/// Reads stdin until it's closed, and passes the bytes on to the listener
+fn read_stdin () {
+  let stdin = std::io::stdin();
+  let mut handle = stdin.lock();
+  let mut bytes = vec![0_u8; STDIN_READ_SIZE];
+
+  loop {
+    let len = match handle.read(&mut bytes) {
+      Ok(0) => break,
+      Ok(len) => len,
+      Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+      Err(_) => break
+    };
+
+    let mut listener = STDIN_LISTENER.lock().unwrap();
+    if listener.as_ref().is_some_and(|tx| tx.send((bytes[0..len].to_vec(), time::Instant::now())).is_err()) {
+      *listener = None; // The source is gone
+    }
+  }
+
+  println!("Stdin has been closed.");
+  *STDIN_LISTENER.lock().unwrap() = None;
+}
+
+/// Listens for UDP datagrams containing raw samples on the given address
+pub fn create_network_source (address: &str, sample_rate: u32, channels: usize) -> Result<ChannelSource, String> {
+  // If we just switched away from this address, the old socket may need
+  // a moment to close, so give it a few tries.
+  let mut socket = UdpSocket::bind(address);
+  for _ in 0..5 {
+    if socket.is_ok() {
+      break;
+    }
+    thread::sleep(time::Duration::from_millis(100));
+    socket = UdpSocket::bind(address);
+  }
+
+  let socket = socket.map_err(|e| format!("Could not listen on {}: {}", address, e))?;
+  // Don't block forever, so that we notice when we should stop
+  socket.set_read_timeout(Some(time::Duration::from_millis(100))).map_err(|e| e.to_string())?;
+
+  println!("Listening for raw samples on {} ...", address);
+
+  Ok(ChannelSource::spawn(format!("Network: {}", address), sample_rate, channels, move |tx, state| {
+    let chunk_len = CHUNK_FRAMES * channels;
+    let mut datagram = vec![0_u8; 65_536];
+    let mut pending: Vec<f32> = Vec::new();
+
+    while !state.is_stopped() {
+      let len = match socket.recv(&mut datagram) {
+        Ok(len) => len,
+        Err(_) => continue // Timeout, check whether we should stop
+      };
+
+      if !state.is_playing() {
+        continue;
+      }
+
+      // Datagrams can have any size, so bring them into shape
+      pending.extend(bytes_to_samples(&datagram[0..len]));
+      while pending.len() >= chunk_len {
+        let chunk: Vec<f32> = pending.drain(0..chunk_len).collect();
+        if tx.send((chunk, time::Instant::now())).is_err() {
+          return; // Nobody's listening anymore
+        }
+      }
+    }
+  }))
+}
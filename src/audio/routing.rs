@@ -0,0 +1,80 @@
+// Channel routing -- decides which of the input channels the renderers display
+
+/// Most renderers have a left and a right side. The routing determines what
+/// ends up on either side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelRouting {
+  Pair(usize, usize), // Two channels, e.g. (0, 1) for the first stereo pair
+  Single(usize), // One channel on both sides
+  Downmix // The average of all channels on both sides
+}
+
+impl ChannelRouting {
+  /// The routing a source starts with: its first pair of channels, or its
+  /// only channel
+  pub fn initial (channels: usize) -> Self {
+    if channels > 1 {
+      ChannelRouting::Pair(0, 1)
+    } else {
+      ChannelRouting::Single(0)
+    }
+  }
+
+  /// All routings for the given amount of channels, in the order the UI
+  /// offers them: the pairs first, then the single channels, then the downmix
+  pub fn all (channels: usize) -> Vec<Self> {
+    let mut routings = Vec::new();
+    for left in (0..channels.saturating_sub(1)).step_by(2) {
+      routings.push(ChannelRouting::Pair(left, left + 1));
+    }
+    for channel in 0..channels {
+      routings.push(ChannelRouting::Single(channel));
+    }
+    if channels > 1 {
+      routings.push(ChannelRouting::Downmix);
+    }
+    routings
+  }
+
+  pub fn name (&self) -> String {
+    match self {
+      ChannelRouting::Pair(left, right) => format!("{} + {}", left + 1, right + 1),
+      ChannelRouting::Single(channel) => format!("{} only", channel + 1),
+      ChannelRouting::Downmix => String::from("Downmix")
+    }
+  }
+
+  /// Picks the left and right side from per-channel data. Channels the
+  /// source doesn't have fall back to the first one, so a mono source shows
+  /// up on both sides. Without any channels, both sides are empty.
+  pub fn route (&self, channels: &[Vec<f32>]) -> [Vec<f32>; 2] {
+    if channels.is_empty() {
+      return [Vec::new(), Vec::new()];
+    }
+
+    let pick = |channel: usize| channels.get(channel).unwrap_or(&channels[0]).clone();
+    match self {
+      ChannelRouting::Pair(left, right) => [pick(*left), pick(*right)],
+      ChannelRouting::Single(channel) => [pick(*channel), pick(*channel)],
+      ChannelRouting::Downmix => {
+        let downmix = downmix(channels);
+        [downmix.clone(), downmix]
+      }
+    }
+  }
+}
+
+/// Averages all channels into one
+pub fn downmix (channels: &[Vec<f32>]) -> Vec<f32> {
+  let length = channels.iter().map(|channel| channel.len()).min().unwrap_or(0);
+  let mut downmix = vec![0.0; length];
+  for channel in channels.iter() {
+    for (sum, value) in downmix.iter_mut().zip(channel.iter()) {
+      *sum += value;
+    }
+  }
+  for sum in downmix.iter_mut() {
+    *sum /= channels.len() as f32;
+  }
+  downmix
+}
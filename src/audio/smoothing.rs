@@ -0,0 +1,125 @@
+// Smoothing -- attack/release ballistics and peak hold for spectrum data
+use super::decibel::DecibelScale;
+
+// The presets offered in the UI
+const PRESETS: [(&str, Ballistics); 4] = [
+  // Without smoothing, the peaks simply follow the spectrum
+  ("Off", Ballistics { attack: 0.0, release: 0.0, peak_hold: 0.0, peak_decay: f32::INFINITY }),
+  ("Fast", Ballistics { attack: 0.005, release: 0.1, peak_hold: 0.5, peak_decay: 24.0 }),
+  ("Medium", Ballistics { attack: 0.01, release: 0.3, peak_hold: 1.0, peak_decay: 12.0 }),
+  ("Slow", Ballistics { attack: 0.05, release: 0.8, peak_hold: 1.5, peak_decay: 6.0 })
+];
+
+/// Describes how fast the smoothed values follow the raw spectrum, and how
+/// the peaks fall back down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ballistics {
+  pub attack: f32, // Time constant in seconds while the level rises
+  pub release: f32, // Time constant in seconds while the level falls
+  pub peak_hold: f32, // How many seconds a peak stays before it decays
+  pub peak_decay: f32 // How fast the peaks fall afterwards, in dB per second
+}
+
+impl Ballistics {
+  /// All presets, in the order the UI offers them
+  pub fn all () -> Vec<Self> {
+    PRESETS.iter().map(|(_name, ballistics)| *ballistics).collect()
+  }
+
+  pub fn name (&self) -> String {
+    match PRESETS.iter().find(|(_name, ballistics)| ballistics == self) {
+      Some((name, _ballistics)) => String::from(*name),
+      None => format!("{} ms / {} ms", self.attack * 1000.0, self.release * 1000.0)
+    }
+  }
+}
+
+impl Default for Ballistics {
+  fn default () -> Self {
+    PRESETS[2].1 // Medium
+  }
+}
+
+/// Keeps the state of each bin (or band) across frames. Both the smoothed
+/// values and the peaks are tracked in dB, since that is how we display
+/// them: a linear release would drop through the lower part of the scale
+/// almost instantly.
+pub struct Smoother {
+  values: Vec<f32>, // in dBFS
+  peaks: Vec<f32>, // in dBFS
+  peak_ages: Vec<f32> // Seconds since each peak was set
+}
+
+impl Smoother {
+  pub fn create () -> Self {
+    Self {
+      values: Vec::new(),
+      peaks: Vec::new(),
+      peak_ages: Vec::new()
+    }
+  }
+
+  /// Feeds the next frame of magnitudes, which arrived `elapsed` seconds
+  /// after the previous one.
+  pub fn process (&mut self, magnitudes: &[f32], elapsed: f32, ballistics: &Ballistics) {
+    if magnitudes.len() != self.values.len() {
+      // New FFT size or band layout, so the old state is meaningless
+      self.values = magnitudes.iter().map(|magnitude| DecibelScale::to_decibels(*magnitude)).collect();
+      self.peaks = self.values.clone();
+      self.peak_ages = vec![0.0; magnitudes.len()];
+      return;
+    }
+
+    let attack = coefficient(ballistics.attack, elapsed);
+    let release = coefficient(ballistics.release, elapsed);
+
+    for (i, magnitude) in magnitudes.iter().enumerate() {
+      let decibels = DecibelScale::to_decibels(*magnitude);
+
+      // Exponential approach towards the new level, faster on the way up
+      let factor = if decibels > self.values[i] { attack } else { release };
+      self.values[i] += (decibels - self.values[i]) * factor;
+
+      // Peaks stay for a while, then fall linearly on the dB scale
+      self.peak_ages[i] += elapsed;
+      if self.peak_ages[i] > ballistics.peak_hold {
+        self.peaks[i] -= ballistics.peak_decay * elapsed;
+      }
+      if self.values[i] >= self.peaks[i] {
+        self.peaks[i] = self.values[i];
+        self.peak_ages[i] = 0.0;
+      }
+    }
+  }
+
+  /// Returns the smoothed magnitudes (linear, just like the raw spectrum)
+  pub fn values (&self) -> Vec<f32> {
+    self.values.iter().map(|decibels| to_magnitude(*decibels)).collect()
+  }
+
+  /// Returns the peak magnitudes (linear, just like the raw spectrum)
+  pub fn peaks (&self) -> Vec<f32> {
+    self.peaks.iter().map(|decibels| to_magnitude(*decibels)).collect()
+  }
+
+  /// Forgets all state, e.g. after switching to a different source
+  pub fn reset (&mut self) {
+    self.values.clear();
+    self.peaks.clear();
+    self.peak_ages.clear();
+  }
+}
+
+/// Calculates how far a value moves towards its target within one step for
+/// the given time constant. Zero means it jumps there immediately.
+fn coefficient (time_constant: f32, elapsed: f32) -> f32 {
+  if time_constant <= 0.0 {
+    1.0
+  } else {
+    1.0 - (-elapsed / time_constant).exp()
+  }
+}
+
+fn to_magnitude (decibels: f32) -> f32 {
+  10_f32.powf(decibels / 20.0)
+}
@@ -0,0 +1,293 @@
+// Audio sources -- everything the analyzer can listen to
+use std::sync::mpsc;
+use mpsc::TryRecvError;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::PathBuf;
+use std::thread;
+use std::time;
+
+use super::generator::{self, Signal, parse_signals, fetch_presets};
+use super::error::AudioError;
+
+// How many frames threaded sources send in one go. This mimics the buffer
+// size of an input device. Any size works, the FrameBuffer cuts the FFT
+// frames out of whatever arrives.
+pub const CHUNK_FRAMES: usize = 1024;
+
+// Defaults for raw PCM sources, which can't tell us their format
+const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+const DEFAULT_CHANNELS: usize = 2;
+
+/**
+ * This defines the contract between the Audio handler and whatever delivers
+ * samples to it. A source always delivers interleaved f32 buffers, so the
+ * analysis doesn't need to care where these come from.
+ */
+pub trait AudioSource {
+  /// A human readable name of the source
+  fn name (&self) -> String;
+
+  /// The sample rate of the delivered samples, e.g. 44,100 Hz
+  fn sample_rate (&self) -> u32;
+
+  /// How many channels are interleaved in each buffer
+  fn channels (&self) -> usize;
+
+  /// Returns the next buffer of interleaved samples, if there is one, and
+  /// when its newest samples were captured (or produced)
+  fn try_recv (&self) -> Result<(Vec<f32>, time::Instant), TryRecvError>;
+
+  /// Starts (or resumes) delivering samples
+  fn play (&self) -> Result<(), AudioError>;
+
+  /// Pauses delivering samples
+  fn pause (&self) -> Result<(), AudioError>;
+
+  /// Returns an error the source has run into while delivering samples. Once
+  /// there is one, the source is most likely unusable.
+  fn error (&self) -> Option<AudioError> {
+    None
+  }
+
+  /// The name of the input device behind the source, if it is one. This is
+  /// how the device is found again after it has been unplugged.
+  fn device_name (&self) -> Option<String> {
+    None
+  }
+
+  /// Returns how many samples the source had to drop since the last call,
+  /// because we didn't fetch them in time
+  fn take_overruns (&self) -> usize {
+    0
+  }
+}
+
+/// What kind of source an AudioDevice stands for
+#[derive(Clone)]
+pub enum SourceKind {
+  Input, // An input device of the default host
+  File(PathBuf), // An audio file which will be played back
+  Stdin(u32, usize), // Raw f32 samples on stdin (sample rate, channels)
+  Network(String, u32, usize), // Raw f32 samples via UDP (address, sample rate, channels)
+  Generator(Vec<Signal>) // Synthetic signals
+}
+
+/// Flags shared between a ChannelSource and the thread feeding it
+#[derive(Clone)]
+pub struct SourceState {
+  playing: Arc<AtomicBool>,
+  stopped: Arc<AtomicBool>
+}
+
+impl SourceState {
+  fn create () -> Self {
+    Self {
+      playing: Arc::new(AtomicBool::new(true)),
+      stopped: Arc::new(AtomicBool::new(false))
+    }
+  }
+
+  /// False while the source is paused
+  pub fn is_playing (&self) -> bool {
+    self.playing.load(Ordering::SeqCst)
+  }
+
+  /// True as soon as the source has been dropped and the feeder should stop
+  pub fn is_stopped (&self) -> bool {
+    self.stopped.load(Ordering::SeqCst)
+  }
+}
+
+/// A source that receives its buffers through a channel, either from a
+/// thread it spawned itself or from anyone holding the sender. Each buffer
+/// is sent together with the moment it was produced.
+pub struct ChannelSource {
+  name: String,
+  sample_rate: u32,
+  channels: usize,
+  receiver: mpsc::Receiver<(Vec<f32>, time::Instant)>,
+  state: SourceState
+}
+
+impl ChannelSource {
+  /// Wraps an existing receiver, e.g. to feed samples by hand
+  pub fn create (name: String, sample_rate: u32, channels: usize, receiver: mpsc::Receiver<(Vec<f32>, time::Instant)>) -> Self {
+    Self {
+      name,
+      sample_rate,
+      channels,
+      receiver,
+      state: SourceState::create()
+    }
+  }
+
+  /// Spawns a thread running the feeder, which sends its buffers to the source
+  pub fn spawn<F> (name: String, sample_rate: u32, channels: usize, feeder: F) -> Self
+  where F: FnOnce(mpsc::Sender<(Vec<f32>, time::Instant)>, SourceState) + Send + 'static {
+    let (tx, rx) = mpsc::channel();
+    let source = Self::create(name, sample_rate, channels, rx);
+    let state = source.state.clone();
+    thread::spawn(move || feeder(tx, state));
+    source
+  }
+}
+
+impl AudioSource for ChannelSource {
+  fn name (&self) -> String {
+    self.name.clone()
+  }
+
+  fn sample_rate (&self) -> u32 {
+    self.sample_rate
+  }
+
+  fn channels (&self) -> usize {
+    self.channels
+  }
+
+  fn try_recv (&self) -> Result<(Vec<f32>, time::Instant), TryRecvError> {
+    self.receiver.try_recv()
+  }
+
+  fn play (&self) -> Result<(), AudioError> {
+    self.state.playing.store(true, Ordering::SeqCst);
+    Ok(())
+  }
+
+  fn pause (&self) -> Result<(), AudioError> {
+    self.state.playing.store(false, Ordering::SeqCst);
+    Ok(())
+  }
+}
+
+impl Drop for ChannelSource {
+  fn drop (&mut self) {
+    // Tell the feeder to wind down
+    self.state.stopped.store(true, Ordering::SeqCst);
+  }
+}
+
+/// Stands in whenever there is nothing to listen to, e.g. on a machine
+/// without any input device or after the device has been unplugged
+pub struct NoSource;
+
+impl AudioSource for NoSource {
+  fn name (&self) -> String {
+    String::from("No input")
+  }
+
+  fn sample_rate (&self) -> u32 {
+    DEFAULT_SAMPLE_RATE
+  }
+
+  fn channels (&self) -> usize {
+    DEFAULT_CHANNELS
+  }
+
+  fn try_recv (&self) -> Result<(Vec<f32>, time::Instant), TryRecvError> {
+    Err(TryRecvError::Empty) // Silence, forever
+  }
+
+  fn play (&self) -> Result<(), AudioError> {
+    Ok(())
+  }
+
+  fn pause (&self) -> Result<(), AudioError> {
+    Ok(())
+  }
+}
+
+/// Keeps threaded sources at real time pace, one chunk of CHUNK_FRAMES at a time
+pub struct Pacer {
+  chunk_duration: time::Duration,
+  next_chunk: time::Instant // The point in time when the next chunk is due
+}
+
+impl Pacer {
+  pub fn create (sample_rate: u32) -> Self {
+    Self {
+      chunk_duration: time::Duration::from_secs_f64(CHUNK_FRAMES as f64 / sample_rate as f64),
+      next_chunk: time::Instant::now()
+    }
+  }
+
+  /// Blocks until the next chunk is due
+  pub fn wait (&mut self) {
+    let now = time::Instant::now();
+    if self.next_chunk > now {
+      thread::sleep(self.next_chunk - now);
+    }
+    self.next_chunk += self.chunk_duration;
+  }
+
+  /// Sleeps for one chunk while paused and restarts the clock afterwards
+  pub fn idle (&mut self) {
+    thread::sleep(self.chunk_duration);
+    self.next_chunk = time::Instant::now();
+  }
+}
+
+/// Returns all sources that have been requested on the command line, followed
+/// by the generator presets. Files are simply passed as paths, raw PCM can be
+/// read with --stdin or --listen <address>. Their format is set with --rate
+/// and --channels. Signals are generated with --generator <spec> (see
+/// generator::Signal::parse for the format). The --host option is handled by
+/// util::initial_host.
+pub fn fetch_sources () -> Vec<(String, SourceKind)> {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+
+  // First pass: The format of raw sources
+  let mut sample_rate = DEFAULT_SAMPLE_RATE;
+  let mut channels = DEFAULT_CHANNELS;
+  for (i, arg) in args.iter().enumerate() {
+    let value = args.get(i + 1).map(String::as_str).unwrap_or_default();
+    match arg.as_str() {
+      // Zero (or anything that isn't a whole number) would stall the pacer
+      "--rate" => match value.parse() {
+        Ok(rate) if rate > 0 => sample_rate = rate,
+        _ => println!("ERROR: Invalid sample rate {}, using {} Hz", value, sample_rate)
+      },
+      "--channels" => match value.parse() {
+        Ok(count) if count > 0 => channels = count,
+        _ => println!("ERROR: Invalid channel count {}, using {}", value, channels)
+      },
+      _ => { /* Not a format argument */ }
+    }
+  }
+
+  // Second pass: The sources themselves
+  let mut ret = Vec::new();
+  let mut i = 0;
+  while i < args.len() {
+    match args[i].as_str() {
+      "--stdin" => ret.push((String::from("Stdin"), SourceKind::Stdin(sample_rate, channels))),
+      "--listen" if i + 1 < args.len() => {
+        i += 1;
+        ret.push((format!("Network: {}", args[i]), SourceKind::Network(args[i].clone(), sample_rate, channels)));
+      },
+      "--generator" if i + 1 < args.len() => {
+        i += 1;
+        match parse_signals(&args[i], generator::SAMPLE_RATE) {
+          Ok(signals) => ret.push((format!("Generator: {}", args[i]), SourceKind::Generator(signals))),
+          Err(e) => println!("ERROR: {}", e)
+        }
+      },
+      "--rate" | "--channels" | "--host" => i += 1, // Skip the value as well
+      arg => {
+        let path = PathBuf::from(arg);
+        if path.is_file() {
+          let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+          ret.push((format!("File: {}", file_name), SourceKind::File(path)));
+        }
+      }
+    }
+    i += 1;
+  }
+
+  for (name, signals) in fetch_presets() {
+    ret.push((name, SourceKind::Generator(signals)));
+  }
+
+  ret
+}
@@ -0,0 +1,23 @@
+// Stream statistics -- how well the analysis keeps up with the source
+
+/// Counts the buffers of the source since the audio handler has been created
+#[derive(Clone, Copy)]
+pub struct StreamStats {
+  pub received: u64, // Buffers the source has delivered
+  pub late: u64, // Buffers that have been dropped, or were older than LATE_THRESHOLD when we fetched them
+  pub dropped: u64, // Buffers that have been skipped because the analysis fell behind
+  pub overruns: u64, // Samples the source had to drop because we didn't fetch them in time
+  pub latency: f32 // The average time (in seconds) from the capture of the samples until they're displayed
+}
+
+impl StreamStats {
+  pub fn create () -> Self {
+    Self {
+      received: 0,
+      late: 0,
+      dropped: 0,
+      overruns: 0,
+      latency: 0.0
+    }
+  }
+}
@@ -0,0 +1,68 @@
+// Stereo -- measures the phase relation and width of the two channels
+
+/// The stereo image of one frame
+#[derive(Clone)]
+pub struct Stereo {
+  pub correlation: f32, // -1.0 (out of phase) over 0.0 (unrelated) to 1.0 (mono), 0.0 when silent
+  pub balance: f32, // -1.0 (only left) to 1.0 (only right), 0.0 when silent
+  pub width: f32, // The level of the side relative to the mid signal, 0.0 for mono
+  pub mid: Vec<f32>, // (L + R) / 2 for each sample
+  pub side: Vec<f32> // (L - R) / 2 for each sample
+}
+
+impl Stereo {
+  /// A silent stereo image
+  pub fn create () -> Self {
+    Self {
+      correlation: 0.0,
+      balance: 0.0,
+      width: 0.0,
+      mid: Vec::new(),
+      side: Vec::new()
+    }
+  }
+}
+
+/// Analyzes the samples of both channels. Mono sources have the same samples
+/// on both channels and therefore end up with a correlation of 1.0.
+pub fn analyze_stereo (left: &[f32], right: &[f32]) -> Stereo {
+  let mut left_energy = 0.0;
+  let mut right_energy = 0.0;
+  let mut product = 0.0;
+  let mut mid = Vec::with_capacity(left.len());
+  let mut side = Vec::with_capacity(left.len());
+  for (l, r) in left.iter().zip(right.iter()) {
+    left_energy += l * l;
+    right_energy += r * r;
+    product += l * r;
+    mid.push((l + r) / 2.0);
+    side.push((l - r) / 2.0);
+  }
+
+  let correlation = if left_energy > 0.0 && right_energy > 0.0 {
+    (product / (left_energy * right_energy).sqrt()).clamp(-1.0, 1.0)
+  } else {
+    0.0
+  };
+
+  // The balance compares the levels, not the energy, of both channels
+  let left_level = left_energy.sqrt();
+  let right_level = right_energy.sqrt();
+  let balance = if left_level + right_level > 0.0 {
+    (right_level - left_level) / (right_level + left_level)
+  } else {
+    0.0
+  };
+
+  let mid_energy: f32 = mid.iter().map(|sample| sample * sample).sum();
+  let side_energy: f32 = side.iter().map(|sample| sample * sample).sum();
+  let width = if mid_energy > 0.0 {
+    (side_energy / mid_energy).sqrt()
+  } else if side_energy > 0.0 {
+    f32::INFINITY // Perfectly out of phase
+  } else {
+    0.0
+  };
+
+  Stereo { correlation, balance, width, mid, side }
+}
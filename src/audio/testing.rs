@@ -0,0 +1,21 @@
+// Test helpers -- known signals from the generator, and comparisons with a tolerance
+use super::generator::{Generator, Signal, Waveform};
+
+/// Generates a signal panned hard left and returns the left channel only
+pub fn mono (waveform: Waveform, frequency: f32, amplitude: f32, sample_rate: u32, frames: usize) -> Vec<f32> {
+  let mut generator = Generator::create(vec![Signal::create(waveform, frequency, amplitude, -1.0)], sample_rate);
+  generator.next_chunk(frames).iter().step_by(2).copied().collect()
+}
+
+/// Generates a signal with the given amplitude on both channels (interleaved)
+pub fn stereo (waveform: Waveform, frequency: f32, amplitude: f32, sample_rate: u32, frames: usize) -> Vec<f32> {
+  // Panned to the center, each channel gets the amplitude divided by sqrt(2)
+  let mut generator = Generator::create(vec![Signal::create(waveform, frequency, amplitude * 2_f32.sqrt(), 0.0)], sample_rate);
+  generator.next_chunk(frames)
+}
+
+/// Fails unless the value is within the tolerance of the expected one
+#[track_caller]
+pub fn assert_close (value: f32, expected: f32, tolerance: f32) {
+  assert!((value - expected).abs() <= tolerance, "{} instead of {} (+/- {})", value, expected, tolerance);
+}
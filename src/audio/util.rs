@@ -1,107 +1,420 @@
 // Audio utility functions -- basically nice to have
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{SupportedStreamConfigRange, StreamConfig};
+use cpal::{Sample, SampleFormat, SupportedStreamConfigRange, StreamConfig, BufferSize, SupportedBufferSize, SampleRate, HostId};
 use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
+use mpsc::TryRecvError;
+use ringbuf::{RingBuffer, Producer, Consumer};
+
+use super::source::{AudioSource, SourceKind, fetch_sources, CHUNK_FRAMES};
+use super::file::create_file_source;
+use super::raw::{create_stdin_source, create_network_source};
+use super::generator::create_generator_source;
+use super::error::AudioError;
 
 // Store information about available audio devices for easy switching
+#[derive(Clone)]
 pub struct AudioDevice {
   pub index: usize,
   pub name: String,
+  pub kind: SourceKind,
   // Save all supported configurations for easy access through the GUI
   pub supported_configurations: Vec<SupportedStreamConfigRange>
 }
 
-/// Fetches all audio devices available on the default host
-pub fn fetch_devices () -> Vec<AudioDevice> {
-  // Get the default host, e.g. CoreAudio, Jack etc.
-  let host = cpal::default_host();
+// Devices mostly support a range of sample rates, of which we offer these
+const SAMPLE_RATES: [u32; 8] = [8_000, 16_000, 22_050, 44_100, 48_000, 88_200, 96_000, 192_000];
+// The same goes for the buffer sizes, in frames
+const BUFFER_SIZES: [u32; 8] = [64, 128, 256, 512, 1_024, 2_048, 4_096, 8_192];
+// The capture callback can get this many seconds ahead of us before it has to drop samples
+const RING_BUFFER_SECONDS: f32 = 1.0;
+// The callback stamps every buffer it receives, and no device calls it with fewer frames than this
+const MIN_CALLBACK_FRAMES: usize = 16;
+
+impl AudioDevice {
+  /// The supported configurations with the given amount of channels
+  fn configurations (&self, channels: u16) -> impl Iterator<Item = &SupportedStreamConfigRange> {
+    self.supported_configurations.iter().filter(move |range| range.channels() == channels)
+  }
+
+  /// All channel counts the device supports, in ascending order
+  pub fn channel_counts (&self) -> Vec<u16> {
+    let mut counts: Vec<u16> = self.supported_configurations.iter().map(|range| range.channels()).collect();
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+  }
+
+  /// All sample rates the device supports with the given amount of channels.
+  /// These are the common sample rates within the supported ranges, plus the
+  /// limits of the ranges themselves.
+  pub fn sample_rates (&self, channels: u16) -> Vec<u32> {
+    let mut rates = Vec::new();
+    for range in self.configurations(channels) {
+      let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+      rates.push(min);
+      rates.push(max);
+      rates.extend(SAMPLE_RATES.iter().filter(|rate| **rate >= min && **rate <= max));
+    }
+    rates.sort_unstable();
+    rates.dedup();
+    rates
+  }
+
+  /// All buffer sizes the device supports with the given amount of channels
+  /// and sample rate. The default one is always possible.
+  pub fn buffer_sizes (&self, channels: u16, sample_rate: u32) -> Vec<BufferSize> {
+    let mut sizes: Vec<u32> = Vec::new();
+    for range in self.configurations(channels) {
+      if range.min_sample_rate().0 > sample_rate || range.max_sample_rate().0 < sample_rate {
+        continue;
+      }
+
+      if let SupportedBufferSize::Range { min, max } = range.buffer_size() {
+        sizes.extend(BUFFER_SIZES.iter().filter(|size| *size >= min && *size <= max));
+      }
+    }
+    sizes.sort_unstable();
+    sizes.dedup();
+
+    let mut buffer_sizes = vec![BufferSize::Default];
+    buffer_sizes.extend(sizes.into_iter().map(BufferSize::Fixed));
+    buffer_sizes
+  }
+
+  /// Returns a configuration the device supports that's as close as possible
+  /// to the given one. If the device doesn't support the amount of channels,
+  /// it falls back to the first one it supports, and the same goes for the
+  /// sample rate and the buffer size. Devices without any configurations
+  /// (such as files) return None.
+  pub fn closest_config (&self, channels: u16, sample_rate: u32, buffer_size: BufferSize) -> Option<StreamConfig> {
+    let channel_counts = self.channel_counts();
+    let channels = if channel_counts.contains(&channels) { channels } else { *channel_counts.first()? };
+
+    let sample_rates = self.sample_rates(channels);
+    let sample_rate = if sample_rates.contains(&sample_rate) { sample_rate } else { *sample_rates.last()? };
+
+    let buffer_size = if self.buffer_sizes(channels, sample_rate).contains(&buffer_size) { buffer_size } else { BufferSize::Default };
+
+    Some(StreamConfig { channels, sample_rate: SampleRate(sample_rate), buffer_size })
+  }
+}
+
+/// The host to start with: the one passed with --host <name> (e.g. --host
+/// jack), or the default one of the platform
+pub fn initial_host () -> HostId {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  if let Some(name) = args.iter().position(|arg| arg == "--host").and_then(|i| args.get(i + 1)) {
+    match cpal::available_hosts().into_iter().find(|host| host.name().eq_ignore_ascii_case(name)) {
+      Some(host) => return host,
+      None => println!("ERROR: The host {} is not available, falling back to the default one", name)
+    }
+  }
+
+  cpal::default_host().id()
+}
+
+/// Fetches all audio devices available on the given host, followed by all
+/// sources that have been passed on the command line. Only fails if the host
+/// is unavailable (e.g. no JACK server is running) or can't list its devices.
+pub fn fetch_devices (host: HostId) -> Result<Vec<AudioDevice>, AudioError> {
+  // Get the host, e.g. CoreAudio, Jack etc.
+  let host = cpal::host_from_id(host)?;
 
   // Save all available devices into our buffer
-  let all_devices = host.input_devices().expect("Could not get a list of available input devices!");
+  let all_devices = host.input_devices()?;
   let mut ret = Vec::new();
 
   for (device_index, device) in all_devices.enumerate() {
-    // There is at least one input configuration available;
-    // let's retrieve all of them
-    let input_configs = device.supported_input_configs().unwrap(); // We can be sure it's NOT an error
-    let mut cfg = Vec::new();
-    for (_idx, config) in input_configs.enumerate() {
-      cfg.push(config);
-    }
+    // Retrieve all input configurations. A device that can't tell us (e.g.
+    // because it's being unplugged right now) is still listed, so the
+    // indices match the ones of the host.
+    let cfg = match device.supported_input_configs() {
+      Ok(input_configs) => input_configs.collect(),
+      Err(e) => {
+        println!("ERROR: {}", AudioError::from(e));
+        Vec::new()
+      }
+    };
 
     // Make the AudioDevice struct
     ret.push(AudioDevice {
       index: device_index,
-      name: device.name().unwrap(),
+      name: device.name().unwrap_or_else(|_| format!("Unknown device {}", device_index + 1)),
+      kind: SourceKind::Input,
       supported_configurations: cfg
     })
   }
 
-  ret // Return the devices
+  // These come after the devices, so the device indices stay the same
+  for (name, kind) in fetch_sources() {
+    ret.push(AudioDevice {
+      index: ret.len(),
+      name,
+      kind,
+      supported_configurations: Vec::new()
+    })
+  }
+
+  Ok(ret) // Return the devices
 }
 
-/// Creates an input listening stream and returns both the stream and the data receiver
-pub fn create_stream (device_index: Option<usize>, device_config: Option<StreamConfig>) -> (impl StreamTrait, StreamConfig, mpsc::Receiver<std::vec::Vec<f32>>, usize) {
-  // The input stream will live in a different thread, so we need a transmitter
-  // to safely transmit data to this (main) thread
-  let (tx, rx) = mpsc::channel();
+/// The name of the default input device of the host, if it has one
+pub fn default_input_name (host: HostId) -> Option<String> {
+  cpal::host_from_id(host).ok()?.default_input_device()?.name().ok()
+}
+
+/// Opens the source behind an entry returned by fetch_devices. Input devices
+/// are opened by name, so it doesn't matter if the indices have shifted since.
+/// The stream config only applies to input devices, all other sources
+/// determine their format themselves.
+pub fn open_source (host: HostId, device: &AudioDevice, device_config: Option<StreamConfig>) -> Result<Box<dyn AudioSource>, AudioError> {
+  match &device.kind {
+    SourceKind::Input => Ok(Box::new(DeviceSource::open_named(host, &device.name, device_config)?)),
+    SourceKind::File(path) => Ok(Box::new(create_file_source(path).map_err(AudioError::Source)?)),
+    SourceKind::Stdin(sample_rate, channels) => Ok(Box::new(create_stdin_source(*sample_rate, *channels))),
+    SourceKind::Network(address, sample_rate, channels) => {
+      Ok(Box::new(create_network_source(address, *sample_rate, *channels).map_err(AudioError::Source)?))
+    },
+    SourceKind::Generator(signals) => Ok(Box::new(create_generator_source(device.name.clone(), signals.clone())))
+  }
+}
 
-  // Get the default host, e.g. CoreAudio, Jack etc.
-  let host = cpal::default_host();
+/// A source listening to an input device. The capture callback writes into
+/// a ring buffer which has been allocated up front, so it never allocates or
+/// waits for a lock. If the ring buffer is full, the callback drops the newest
+/// samples and counts them as an overrun.
+pub struct DeviceSource {
+  name: String,
+  stream: cpal::Stream,
+  config: StreamConfig,
+  consumer: RefCell<Consumer<f32>>, // The reading end of the ring buffer
+  stamps: RefCell<Consumer<(u64, Instant)>>, // For every callback, how many samples have been written up to its end and when it ran
+  samples_read: Cell<u64>, // How many samples we took from the ring buffer so far
+  overruns: Arc<AtomicUsize>, // Samples the callback dropped since the last take_overruns
+  errors: mpsc::Receiver<AudioError> // Errors of the running stream, e.g. if the device is unplugged
+}
 
-  // By default, use the default input device, unless the user has provided a device_index
+impl DeviceSource {
+  /// Opens the device of the host (or its default one) and returns the source and the real device index
+  pub fn open (host: HostId, device_index: Option<usize>, device_config: Option<StreamConfig>) -> Result<(Self, usize), AudioError> {
+    // Get the host, e.g. CoreAudio, Jack etc.
+    let host = cpal::host_from_id(host)?;
 
-  let mut real_idx = 0;
+    // By default, use the default input device, unless the user has provided a device_index
+    let (device, real_idx) = match device_index {
+      Some(idx) => match host.input_devices()?.nth(idx) {
+        Some(device) => (device, idx),
+        None => (host.input_devices()?.next().ok_or(AudioError::NoDevice)?, 0)
+      },
+      None => (host.default_input_device().ok_or(AudioError::NoDevice)?, 0)
+    };
 
-  let device = match device_index {
-    Some (idx) => {
-      let mut all_input_devices = host.input_devices().expect("Could not get a list of available input devices!");
-      real_idx = idx;
-      all_input_devices.nth(idx).unwrap_or_else(|| {
-        real_idx = 0;
-        all_input_devices.nth(real_idx).unwrap()
-      })
-    },
-    None => {
-      host.default_input_device().expect("No device!")
+    Ok((create_stream(device, device_config)?, real_idx))
+  }
+
+  /// Opens the device of the host with the given name
+  pub fn open_named (host: HostId, name: &str, device_config: Option<StreamConfig>) -> Result<Self, AudioError> {
+    // Only the names are compared, which (unlike input_devices) doesn't
+    // query the configurations of every device
+    let device = cpal::host_from_id(host)?.devices()?
+      .find(|device| device.name().is_ok_and(|other| other == name))
+      .ok_or_else(|| AudioError::Device(format!("{} is not available", name)))?;
+    create_stream(device, device_config)
+  }
+}
+
+impl AudioSource for DeviceSource {
+  fn name (&self) -> String {
+    self.name.clone()
+  }
+
+  fn sample_rate (&self) -> u32 {
+    self.config.sample_rate.0
+  }
+
+  fn channels (&self) -> usize {
+    self.config.channels as usize
+  }
+
+  /// Returns up to CHUNK_FRAMES frames at a time, so a backlog still arrives
+  /// in several buffers
+  fn try_recv (&self) -> Result<(Vec<f32>, Instant), TryRecvError> {
+    let mut consumer = self.consumer.borrow_mut();
+    let channels = self.channels().max(1);
+    // Only take whole frames, the rest of the last one is still being written
+    let available = (consumer.len() / channels).min(CHUNK_FRAMES) * channels;
+    if available == 0 {
+      return Err(TryRecvError::Empty);
     }
-  };
+
+    let mut buf = vec![0.0; available];
+    consumer.pop_slice(&mut buf);
+    let samples_read = self.samples_read.get() + available as u64;
+    self.samples_read.set(samples_read);
+
+    // The newest of these samples came with the first callback that wrote up
+    // to (or past) them. The ones before are done with, but a callback we've
+    // only read partly is needed again for the rest of its samples.
+    let mut stamps = self.stamps.borrow_mut();
+    let mut captured_at = None;
+    loop {
+      let next = stamps.iter().next().copied();
+      let (written, at) = match next {
+        Some(stamp) => stamp,
+        None => break
+      };
+      captured_at = Some(at);
+      if written > samples_read {
+        break;
+      }
+      stamps.pop();
+      if written == samples_read {
+        break;
+      }
+    }
+
+    Ok((buf, captured_at.unwrap_or_else(Instant::now)))
+  }
+
+  fn play (&self) -> Result<(), AudioError> {
+    Ok(self.stream.play()?)
+  }
+
+  fn pause (&self) -> Result<(), AudioError> {
+    Ok(self.stream.pause()?)
+  }
+
+  fn error (&self) -> Option<AudioError> {
+    self.errors.try_recv().ok()
+  }
+
+  fn device_name (&self) -> Option<String> {
+    Some(self.name.clone())
+  }
+
+  fn take_overruns (&self) -> usize {
+    self.overruns.swap(0, Ordering::SeqCst)
+  }
+}
+
+/// Creates an input listening stream on the device and returns the source receiving its data
+fn create_stream (device: cpal::Device, device_config: Option<StreamConfig>) -> Result<DeviceSource, AudioError> {
+  // The input stream will live in a different thread, so we need a
+  // transmitter to safely transmit errors to this (main) thread. The samples
+  // take the ring buffer, which we can only allocate once we know the config.
+  let (error_tx, error_rx) = mpsc::channel();
 
   // Now some debug output stuff etc.
-  let name = device.name();
-  println!("Listening to device {} ...", name.unwrap());
+  let name = device.name()?;
+  println!("Listening to device {} ...", name);
 
-  let supported_configs_range = device.supported_input_configs()
-    .expect("error while querying configs");
+  let supported_configs_range = device.supported_input_configs()?;
 
   for (idx, config) in supported_configs_range.enumerate() {
     println!("Supported config {}: {:?}", idx, config);
   }
 
-  let config = match device_config {
-    Some (cfg) => { cfg },
+  let (config, sample_format) = match device_config {
+    Some (cfg) => {
+      let sample_format = find_sample_format(&device, &cfg)?;
+      (cfg, sample_format)
+    },
     // Just use the default
-    None => { device.supported_input_configs().unwrap().next().unwrap().with_max_sample_rate().config() }
+    None => {
+      let supported = device.supported_input_configs()?.next()
+        .ok_or_else(|| AudioError::Config(format!("{} has no input configurations", name)))?
+        .with_max_sample_rate();
+      (supported.config(), supported.sample_format())
+    }
   };
+  println!("Using sample format {:?}", sample_format);
+
+  let capacity = ((config.sample_rate.0 as f32 * RING_BUFFER_SECONDS) as usize).max(CHUNK_FRAMES) * config.channels as usize;
+  let (producer, consumer) = RingBuffer::new(capacity).split();
+  let (stamp_producer, stamps) = RingBuffer::new(capacity / config.channels as usize / MIN_CALLBACK_FRAMES + 1).split();
+  let overruns = Arc::new(AtomicUsize::new(0));
 
+  // The callback needs to know the type of the samples, but we always hand
+  // out f32 samples
+  let stream = match sample_format {
+    SampleFormat::I16 => build_input_stream::<i16>(&device, &config, producer, stamp_producer, overruns.clone(), error_tx),
+    SampleFormat::U16 => build_input_stream::<u16>(&device, &config, producer, stamp_producer, overruns.clone(), error_tx),
+    SampleFormat::F32 => build_input_stream::<f32>(&device, &config, producer, stamp_producer, overruns.clone(), error_tx)
+  }?;
+
+  stream.play()?;
+  Ok(DeviceSource {
+    name,
+    stream,
+    config,
+    consumer: RefCell::new(consumer),
+    stamps: RefCell::new(stamps),
+    samples_read: Cell::new(0),
+    overruns,
+    errors: error_rx
+  })
+}
+
+/// A stream configuration doesn't contain the sample format, so we look for
+/// a supported configuration with the same channels and sample rate. If there
+/// are several, we prefer f32, which needs no conversion.
+fn find_sample_format (device: &cpal::Device, config: &StreamConfig) -> Result<SampleFormat, AudioError> {
+  let mut formats: Vec<SampleFormat> = device.supported_input_configs()?
+    .filter(|range| {
+      range.channels() == config.channels
+        && range.min_sample_rate() <= config.sample_rate
+        && range.max_sample_rate() >= config.sample_rate
+    })
+    .map(|range| range.sample_format())
+    .collect();
+
+  if formats.contains(&SampleFormat::F32) {
+    Ok(SampleFormat::F32)
+  } else if let Some(format) = formats.pop() {
+    Ok(format)
+  } else {
+    // Nothing matches, so let's hope the default format works
+    Ok(device.default_input_config()?.sample_format())
+  }
+}
+
+/// Builds the input stream for samples of type T, which converts the samples
+/// to normalized f32 while writing them into the ring buffer, and stamps them
+fn build_input_stream<T: Sample> (device: &cpal::Device, config: &StreamConfig, mut producer: Producer<f32>, mut stamps: Producer<(u64, Instant)>, overruns: Arc<AtomicUsize>, error_tx: mpsc::Sender<AudioError>) -> Result<cpal::Stream, AudioError> {
+  let channels = config.channels as usize;
+  let mut written: u64 = 0;
   let stream = device.build_input_stream(
-    &config,
-    move |data: &[f32], _: &cpal::InputCallbackInfo| {
-      // Here's when we get a full buffer.
-      // We need to take ownership of the full slice which
-      // we are doing with a vector, and send it to the
-      // main thread
-      let mut safe_buffer = Vec::new();
-      for elem in data {
-        safe_buffer.push(*elem);
+    config,
+    move |data: &[T], _: &cpal::InputCallbackInfo| {
+      // Here's when we get a full buffer. This runs on the realtime thread of
+      // the audio system, so no allocating, locking or printing in here.
+      // If we're behind, only whole frames fit, the rest is dropped.
+      let fitting = (producer.remaining() / channels * channels).min(data.len());
+      // The stamp goes first, so there's one for every sample that can be read
+      written += fitting as u64;
+      let _ = stamps.push((written, Instant::now()));
+      producer.push_iter(&mut data[..fitting].iter().map(|elem| elem.to_f32()));
+      if fitting < data.len() {
+        overruns.fetch_add(data.len() - fitting, Ordering::Relaxed);
       }
-      tx.send(safe_buffer).unwrap();
     },
     move |err| {
-      println!("ERROR: {}", err);
+      // The main thread decides what to do about it
+      let _ = error_tx.send(AudioError::from(err));
     },
-  ).unwrap();
+  )?;
+  Ok(stream)
+}
 
-  stream.play().expect("Could not start stream!");
-  (stream, config, rx, real_idx) // Return the stream and the receiver
+/// Formats a frequency for display, e.g. 440 Hz or 12.5 kHz
+pub fn format_frequency (frequency: f32) -> String {
+  if frequency >= 1_000.0 {
+    format!("{} kHz", (frequency / 100.0).round() / 10.0)
+  } else {
+    format!("{} Hz", frequency.round())
+  }
 }
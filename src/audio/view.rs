@@ -0,0 +1,31 @@
+// Spectrum views -- the spectral representations the renderers can switch between
+use super::mel::MEL_BAND_COUNTS;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpectrumView {
+  Linear, // The FFT bins as they are
+  Bands, // The log-spaced bands of the band layout
+  Mel(usize), // A mel filterbank with the given amount of bands
+  ConstantQ // One bin per semitone
+}
+
+impl SpectrumView {
+  /// All views, in the order the UI offers them
+  pub fn all () -> Vec<Self> {
+    let mut views = vec![SpectrumView::Linear, SpectrumView::Bands];
+    for count in MEL_BAND_COUNTS.iter() {
+      views.push(SpectrumView::Mel(*count));
+    }
+    views.push(SpectrumView::ConstantQ);
+    views
+  }
+
+  pub fn name (&self) -> String {
+    match self {
+      SpectrumView::Linear => String::from("Linear"),
+      SpectrumView::Bands => String::from("Log bands"),
+      SpectrumView::Mel(count) => format!("Mel ({} bands)", count),
+      SpectrumView::ConstantQ => String::from("Constant-Q")
+    }
+  }
+}
@@ -0,0 +1,81 @@
+// Window functions -- applied to each buffer before the FFT to reduce spectral leakage
+use std::f32::consts::PI;
+
+// The Kaiser window offered in the UI. 8.6 roughly matches Blackman-Harris.
+const DEFAULT_KAISER_BETA: f32 = 8.6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowFunction {
+  Rectangular, // No window at all
+  Hann,
+  Hamming,
+  BlackmanHarris, // Four term Blackman-Harris
+  FlatTop, // Very accurate amplitudes, but wide peaks
+  Kaiser(f32) // With the given beta
+}
+
+impl WindowFunction {
+  /// All window functions, in the order the UI offers them
+  pub fn all () -> Vec<Self> {
+    vec![
+      WindowFunction::Rectangular,
+      WindowFunction::Hann,
+      WindowFunction::Hamming,
+      WindowFunction::BlackmanHarris,
+      WindowFunction::FlatTop,
+      WindowFunction::Kaiser(DEFAULT_KAISER_BETA)
+    ]
+  }
+
+  pub fn name (&self) -> String {
+    match self {
+      WindowFunction::Rectangular => String::from("Rectangular"),
+      WindowFunction::Hann => String::from("Hann"),
+      WindowFunction::Hamming => String::from("Hamming"),
+      WindowFunction::BlackmanHarris => String::from("Blackman-Harris"),
+      WindowFunction::FlatTop => String::from("Flat top"),
+      WindowFunction::Kaiser(beta) => format!("Kaiser (beta {})", beta)
+    }
+  }
+
+  /// Calculates the window coefficients for a buffer of the given length.
+  /// We use the periodic form, which is the right one for spectral analysis.
+  pub fn coefficients (&self, len: usize) -> Vec<f32> {
+    let n = len as f32;
+    (0..len).map(|i| {
+      let x = 2.0 * PI * i as f32 / n; // 0 to 2 PI over the window
+      match self {
+        WindowFunction::Rectangular => 1.0,
+        WindowFunction::Hann => 0.5 - 0.5 * x.cos(),
+        WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+        WindowFunction::BlackmanHarris => {
+          0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos()
+        },
+        WindowFunction::FlatTop => {
+          0.215_578_95 - 0.416_631_58 * x.cos() + 0.277_263_16 * (2.0 * x).cos()
+            - 0.083_578_95 * (3.0 * x).cos() + 0.006_947_368 * (4.0 * x).cos()
+        },
+        WindowFunction::Kaiser(beta) => {
+          let ratio = 2.0 * i as f32 / n - 1.0; // -1.0 to 1.0 over the window
+          bessel_i0(beta * (1.0 - ratio * ratio).sqrt()) / bessel_i0(*beta)
+        }
+      }
+    }).collect()
+  }
+}
+
+/// The zeroth order modified Bessel function of the first kind, needed for
+/// the Kaiser window. The series converges quickly for the betas we use.
+fn bessel_i0 (x: f32) -> f32 {
+  let mut sum = 1.0;
+  let mut term = 1.0;
+  let half = x / 2.0;
+  for k in 1..50 {
+    term *= (half / k as f32) * (half / k as f32);
+    sum += term;
+    if term < sum * 1e-8 {
+      break;
+    }
+  }
+  sum
+}
@@ -0,0 +1,186 @@
+use crate::traits::RendererBase;
+use piston::input::{UpdateArgs, RenderArgs, Key};
+use opengl_graphics::GlGraphics;
+use graphics::{Context, Transformed};
+
+use graphics::{ellipse, polygon, text};
+use graphics::character::CharacterCache;
+
+// Font imports
+use opengl_graphics::GlyphCache;
+use opengl_graphics::TextureSettings;
+
+use std::f64::consts::PI;
+
+use crate::audio::AnalyzedAudio;
+use crate::audio::chroma::NOTE_NAMES;
+use crate::user_interface::util::find_font;
+
+// How many points make up the arc of each segment
+const ARC_STEPS: usize = 8;
+
+/**
+ * Each renderer consists of three things. First, the struct defining its
+ * state. Secondly, an impl that defines the specific methods of the struct
+ * that won't be called by the application. And third, the trait implementation
+ * which defines all methods that are necessary as the application expects them.
+ */
+pub struct ChromaWheel {
+  width: u32,
+  height: u32,
+  font: GlyphCache<'static>,
+  chroma: [f32; 12], // The displayed chroma, which follows the analyzed one
+  rotation: f64 // in radians, turns the strongest pitch class to the top
+}
+
+impl ChromaWheel {
+  pub fn create () -> Self {
+    Self {
+      width: 200,
+      height: 200,
+      font: GlyphCache::new(find_font().unwrap(), (), TextureSettings::new()).unwrap(),
+      chroma: [0.0; 12],
+      rotation: 0.0
+    }
+  }
+
+  /// Helper function to calculate a color based on the hue
+  fn hue_to_rgb (hue: f32) -> [f32; 4] {
+    let hue_segment = (hue as f64 / 60.0).trunc() as u32 + 1; // Get one of the six segments
+    let fraction: f32 = hue % 60.0 / 60.0;
+
+    let mut rgba: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    // LOOK AT MA MATH SKILLZ!!
+    match hue_segment {
+      1 => {
+        rgba[0] = 1.0;
+        rgba[1] = fraction;
+        rgba[2] = 0.0;
+      },
+      2 => {
+        rgba[0] = fraction;
+        rgba[1] = 1.0;
+        rgba[2] = 0.0;
+      },
+      3 => {
+        rgba[0] = 0.0;
+        rgba[1] = 1.0;
+        rgba[2] = fraction;
+      },
+      4 => {
+        rgba[0] = 0.0;
+        rgba[1] = fraction;
+        rgba[2] = 1.0;
+      },
+      5 => {
+        rgba[0] = fraction;
+        rgba[1] = 0.0;
+        rgba[2] = 1.0;
+      },
+      _ => {
+        rgba[0] = 1.0;
+        rgba[1] = 0.0;
+        rgba[2] = fraction;
+      } // Includes segment 6 and weird other numbers
+    }
+
+    rgba
+  }
+}
+
+impl RendererBase for ChromaWheel {
+  fn render (&mut self, gl: &mut GlGraphics, context: Context, args: &RenderArgs, audio: &AnalyzedAudio) {
+
+    // Always make sure to use the correct sizes to calculate with
+    self.width = args.draw_size[0];
+    self.height = args.draw_size[1];
+
+    let max_radius = self.width.min(self.height) as f64 * 0.4;
+    let min_radius = max_radius * 0.3;
+    let label_size = (max_radius * 0.1).max(10.0) as u32;
+    let segment_angle = 2.0 * PI / 12.0;
+
+    // Let the chroma fall slowly, so chords stay visible
+    for (displayed, analyzed) in self.chroma.iter_mut().zip(audio.chroma.iter()) {
+      let factor = if analyzed > displayed { 0.5 } else { 0.1 };
+      *displayed += (analyzed - *displayed) * factor;
+    }
+
+    // The segments are arranged in the circle of fifths, so related keys are
+    // next to each other: C, G, D, A, E, B, F#, C#, G#, D#, A#, F
+    let mut strongest = 0;
+    for position in 0..12 {
+      if self.chroma[position * 7 % 12] > self.chroma[strongest * 7 % 12] {
+        strongest = position;
+      }
+    }
+
+    // Turn the wheel towards the strongest pitch class, the short way round
+    let target = -(strongest as f64) * segment_angle;
+    let mut difference = (target - self.rotation) % (2.0 * PI);
+    if difference > PI {
+      difference -= 2.0 * PI;
+    } else if difference < -PI {
+      difference += 2.0 * PI;
+    }
+    self.rotation += difference * 0.05;
+
+    let centered_matrix = context.transform.trans(self.width as f64 / 2.0, self.height as f64 / 2.0).rot_rad(self.rotation);
+
+    for position in 0..12 {
+      let pitch_class = position * 7 % 12;
+      let value = self.chroma[pitch_class] as f64;
+      let radius = min_radius + value * (max_radius - min_radius);
+
+      // Each segment is centered around its angle, and 0 is at the top
+      let start = position as f64 * segment_angle - segment_angle / 2.0 - PI / 2.0;
+      let mut points = vec![[0.0, 0.0]];
+      for step in 0..=ARC_STEPS {
+        let angle = start + step as f64 / ARC_STEPS as f64 * segment_angle * 0.95; // Leave a small gap
+        points.push([angle.cos() * radius, angle.sin() * radius]);
+      }
+
+      let mut color = Self::hue_to_rgb(position as f32 * 30.0);
+      color[3] = 0.3 + 0.7 * value as f32;
+      polygon(color, &points, centered_matrix, gl);
+
+      // The label sits outside the wheel, but stays upright
+      let center_angle = start + segment_angle * 0.475;
+      let label = NOTE_NAMES[pitch_class];
+      let label_width = self.font.width(label_size, label).unwrap();
+      let label_x = center_angle.cos() * (max_radius + label_size as f64);
+      let label_y = center_angle.sin() * (max_radius + label_size as f64);
+      text::Text::new_color([1.0, 1.0, 1.0, 0.5 + 0.5 * value as f32], label_size).draw(
+        label,
+        &mut self.font,
+        &context.draw_state,
+        centered_matrix.trans(label_x, label_y).rot_rad(-self.rotation).trans(-label_width / 2.0, label_size as f64 / 2.0),
+        gl
+      ).unwrap();
+    }
+
+    // Finally, hollow out the center
+    ellipse([0.0, 0.0, 0.0, 1.0], [-min_radius, -min_radius, min_radius * 2.0, min_radius * 2.0], centered_matrix, gl);
+  }
+
+  fn update (&mut self, _args: &UpdateArgs) {
+    // Everything happens during rendering
+  }
+
+  fn on_cursor_movement (&mut self, _x: f64, _y: f64) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_cursor_state (&mut self, _is_over_window: bool) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_click (&mut self) {
+    // Don't react to anything
+  }
+
+  fn on_keypress (&mut self, _key: Key) {
+    // Stoic renderer, I tell you
+  }
+}
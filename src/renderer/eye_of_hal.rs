@@ -6,6 +6,7 @@ use graphics::{Context, Transformed};
 use graphics::rectangle;
 
 use crate::audio::AnalyzedAudio;
+use crate::audio::routing::ChannelRouting;
 
 /**
  * Each renderer consists of three things. First, the struct defining its
@@ -16,11 +17,11 @@ use crate::audio::AnalyzedAudio;
 pub struct EyeOfHAL {
   width: u32,
   height: u32,
-  hue: u32,
+  hue: f32,
   zoom_factor: f64,
-  zoom_dir: f64,
   max_zoom: f64,
-  min_zoom: f64
+  min_zoom: f64,
+  beat_count: Option<u64> // The beat_count of the last frame, none before the first one
 }
 
 impl EyeOfHAL {
@@ -28,11 +29,11 @@ impl EyeOfHAL {
     Self {
       width: 200,
       height: 200,
-      hue: 0,
-      zoom_factor: 0.4,
-      zoom_dir: 0.01,
+      hue: 0.0,
+      zoom_factor: 0.35,
       max_zoom: 0.45,
-      min_zoom: 0.35
+      min_zoom: 0.35,
+      beat_count: None
     }
   }
 
@@ -88,33 +89,39 @@ impl RendererBase for EyeOfHAL {
     self.width = args.draw_size[0];
     self.height = args.draw_size[1];
 
-    let mut moving_hue = self.hue as f32;
+    // Pulse on the beat. We may render less often than the audio is
+    // analyzed, so this catches beats in frames we never saw. The zoom then
+    // falls back during update.
+    if self.beat_count.is_some_and(|count| count != audio.beat_count) {
+      self.zoom_factor = self.max_zoom;
+    }
+    self.beat_count = Some(audio.beat_count);
+
+    // The colors follow the timbre: dark sounds are blue, bright ones red
+    let target_hue = (1.0 - audio.features.brightness()) * 240.0;
+    self.hue += (target_hue - self.hue) * 0.05;
+
+    let mut moving_hue = self.hue;
+
+    // The routing decides which channels we display
+    let spectrum = audio.routed(&audio.smoothed_frequency);
+    let peaks = audio.routed(&audio.frequency_peaks);
+    let amplitude = audio.routed(&audio.amplitude);
 
     // We don't want 22kHz displayed as this would be WAY too unreasonable,
     // so we need to find the correct cutoff frequency for which to perform
     // some crude lowpass filter.
     // f / bin = i
     let mut cutoff = (20_000.0 / audio.bin_frequency).floor() as usize;
-    if cutoff > audio.frequency[0].len() {
-      cutoff = audio.frequency[0].len();
+    if cutoff > spectrum[0].len() {
+      cutoff = spectrum[0].len();
     }
 
-    let mut max_height = 0.0;
-    if self.height > self.width {
-      max_height = self.height as f64 * 0.7;
+    let max_height = if self.height > self.width {
+      self.height as f64 * 0.7
     } else {
-      max_height = self.width as f64 * 0.7;
-    }
-
-    // Second, calculate the maximum number (= max height)
-    let mut max_amplitude = 0.0;
-    for sample in audio.frequency[0][0..cutoff].iter() {
-      if sample.abs() > max_amplitude {
-        max_amplitude = sample.abs();
-      }
-    }
-
-    let max_amplitude = max_amplitude as f64;
+      self.width as f64 * 0.7
+    };
 
     let pos_x = self.width as f64 / 2.0;
     let pos_y = self.height as f64 / 2.0;
@@ -130,16 +137,18 @@ impl RendererBase for EyeOfHAL {
       let deg = i as f64 / cutoff as f64 * 360.0;
 
       let col = Self::hue_to_rgb(moving_hue);
-      let mut degree: f64 = audio.frequency[0][i] as f64 / max_amplitude; // val from 0.0-1.0
-      if degree > 1.0 {
-        degree = 1.0;
-      }
+      let degree = audio.decibel_scale.normalize(spectrum[0][i]) as f64; // val from 0.0-1.0
       let height = degree * max_height;
 
       rectangle(col, [pos_x, pos_y, 5.0, height], centered_matrix.zoom(self.zoom_factor).rot_rad(deg), gl);
+
+      // The peak sits at the tip of the spike and slowly falls back towards the center
+      let peak = audio.decibel_scale.normalize(peaks[0][i]) as f64 * max_height;
+      rectangle(col, [pos_x, pos_y + peak, 5.0, 2.0], centered_matrix.zoom(self.zoom_factor).rot_rad(deg), gl);
     }
 
-    if audio.channels > 1 {
+    // Only a pair of channels has a second side to show
+    if audio.channels > 1 && matches!(audio.routing, ChannelRouting::Pair(..)) {
       for i in 0..cutoff {
         moving_hue += 1.0;
         if moving_hue > 360.0 {
@@ -150,35 +159,26 @@ impl RendererBase for EyeOfHAL {
         let deg = deg + 2.0; // offset to mono channel
 
         let col = Self::hue_to_rgb(moving_hue);
-        let mut degree: f64 = audio.frequency[1][i] as f64 / max_amplitude; // val from 0.0-1.0
-        if degree > 1.0 {
-          degree = 1.0;
-        }
+        let degree = audio.decibel_scale.normalize(spectrum[1][i]) as f64; // val from 0.0-1.0
         let height = degree * max_height;
 
         rectangle(col, [pos_x, pos_y, 5.0, height], centered_matrix.zoom(self.zoom_factor).rot_rad(deg), gl);
+
+        // The peak sits at the tip of the spike and slowly falls back towards the center
+        let peak = audio.decibel_scale.normalize(peaks[1][i]) as f64 * max_height;
+        rectangle(col, [pos_x, pos_y + peak, 5.0, 2.0], centered_matrix.zoom(self.zoom_factor).rot_rad(deg), gl);
       }
     }
 
-    for (i, sample) in audio.amplitude[0].iter().enumerate() {
-      let deg = i as f64 / audio.amplitude[0].len() as f64 * 360.0;
-      rectangle(Self::hue_to_rgb(self.hue as f32), [0.0, 0.0, 1.0, sample.abs() as f64 * 600.0], centered_matrix.rot_rad(deg), gl);
+    for (i, sample) in amplitude[0].iter().enumerate() {
+      let deg = i as f64 / amplitude[0].len() as f64 * 360.0;
+      rectangle(Self::hue_to_rgb(self.hue), [0.0, 0.0, 1.0, sample.abs() as f64 * 600.0], centered_matrix.rot_rad(deg), gl);
     }
   }
 
   fn update (&mut self, _args: &UpdateArgs) {
-    // Also update the hue
-    self.hue += 1;
-    if self.hue > 360 {
-      self.hue = 0;
-    }
-
-    self.zoom_factor += self.zoom_dir;
-    if self.zoom_factor > self.max_zoom {
-      self.zoom_dir = -0.00001;
-    } else if self.zoom_factor < self.min_zoom {
-      self.zoom_dir = 0.00001;
-    }
+    // Relax towards the minimum zoom until the next beat comes along
+    self.zoom_factor += (self.min_zoom - self.zoom_factor) * 0.1;
   }
 
   fn on_cursor_movement (&mut self, _x: f64, _y: f64) {
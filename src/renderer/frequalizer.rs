@@ -80,34 +80,30 @@ impl RendererBase for Frequalizer {
     self.width = args.draw_size[0];
     self.height = args.draw_size[1];
 
-    if audio.frequency[0].is_empty() {
+    // The routing decides which channels end up on the left and right
+    let spectrum = audio.routed(&audio.smoothed_spectrum);
+    let peaks = audio.routed(&audio.spectrum_peaks);
+    let amplitude = audio.routed(&audio.amplitude);
+
+    if spectrum[0].is_empty() {
       return; // Nothing to render
     }
 
-    let mut moving_hue = self.hue;
-
-    // We don't want 22kHz displayed as this would be WAY too unreasonable,
-    // so we need to find the correct cutoff frequency for which to perform
-    // some crude lowpass filter.
-    // f / bin = i
-    let mut cutoff = (20_000.0 / audio.bin_frequency).floor() as usize;
-    if cutoff > audio.frequency[0].len() {
-      cutoff = audio.frequency[0].len();
-    }
+    // The colors follow the timbre: dark sounds are blue, bright ones red
+    let target_hue = (1.0 - audio.features.brightness()) * 240.0;
+    self.hue += (target_hue - self.hue) * 0.05;
 
-    let rectangle_width: f64 = self.width as f64 / cutoff as f64;
+    let mut moving_hue = self.hue;
 
-    // Second, calculate the maximum number (= max height)
-    let mut max_amplitude = 0.0;
-    for sample in audio.frequency[0][0..cutoff].iter() {
-      if sample.abs() > max_amplitude {
-        max_amplitude = sample.abs();
-      }
-    }
+    // We display the selected view of the spectrum. We only need to skip the
+    // bands beyond the Nyquist frequency, as they will always be empty.
+    let nyquist = audio.sample_rate as f32 / 2.0;
+    let band_count = audio.spectrum_frequencies().iter().filter(|frequency| **frequency < nyquist).count();
 
-    let max_amplitude = max_amplitude as f64;
+    let rectangle_width: f64 = self.width as f64 / band_count as f64;
+    let peak_height: f64 = (self.height as f64 * 0.005).max(1.0);
 
-    for i in 0..cutoff {
+    for i in 0..band_count {
       moving_hue += 1.0;
       if moving_hue > 360.0 {
         moving_hue = 0.0;
@@ -115,16 +111,9 @@ impl RendererBase for Frequalizer {
 
       let col = Self::hue_to_rgb(moving_hue);
 
-      let amplitude_left = audio.frequency[0][i] as f64;
-      let amplitude_right = audio.frequency[0][i] as f64;
-      let mut degree_left: f64 = amplitude_left / max_amplitude; // val from 0.0-1.0
-      let mut degree_right: f64 = amplitude_right / max_amplitude;
-      if degree_left > 1.0 {
-        degree_left = 1.0;
-      }
-      if degree_right > 1.0 {
-        degree_right = 1.0;
-      }
+      // The level on the dB scale, from 0.0-1.0
+      let degree_left = audio.decibel_scale.normalize(spectrum[0][i]) as f64;
+      let degree_right = audio.decibel_scale.normalize(spectrum[1][i]) as f64;
 
       let posx = i as f64 * rectangle_width;
       let posy_left = (1.0 - degree_left) * self.height as f64;
@@ -134,21 +123,26 @@ impl RendererBase for Frequalizer {
 
       rectangle(col, [posx, posy_left, rectangle_width, height_left], context.transform, gl);
       rectangle(col, [posx, posy_right, rectangle_width, height_right], context.transform, gl);
+
+      // The peak caps slowly fall back down onto the bars
+      for peak in [peaks[0][i], peaks[1][i]].iter() {
+        let posy_peak = (1.0 - audio.decibel_scale.normalize(*peak) as f64) * self.height as f64;
+        rectangle([1.0, 1.0, 1.0, 1.0], [posx, posy_peak, rectangle_width, peak_height], context.transform, gl);
+      }
     }
 
     // Next, display the waveform in form of points from center
     let center_screen: f64 = self.height as f64 / 2.0;
     let ratio = self.height as f64 * 0.2;
-    for i in 0..audio.amplitude[0].len() {
-      rectangle([1.0, 1.0, 1.0, 1.0], [i as f64 * rectangle_width, center_screen + audio.amplitude[0][i] as f64 * ratio, rectangle_width, rectangle_width], context.transform, gl);
+    let point_width = self.width as f64 / amplitude[0].len() as f64;
+    let brightness = if audio.beat { 1.0 } else { 0.5 + 0.5 * audio.onset_strength }; // Flashes on onsets and beats
+    for (i, sample) in amplitude[0].iter().enumerate() {
+      rectangle([brightness, brightness, brightness, 1.0], [i as f64 * point_width, center_screen + *sample as f64 * ratio, point_width.max(1.0), point_width.max(1.0)], context.transform, gl);
     }
   }
 
   fn update (&mut self, _args: &UpdateArgs) {
-    self.hue += 0.1;
-    if self.hue > 360.0 {
-      self.hue = 0.0;
-    }
+    // Everything happens during rendering
   }
 
   fn on_cursor_movement (&mut self, _x: f64, _y: f64) {
@@ -0,0 +1,175 @@
+use crate::traits::RendererBase;
+use piston::input::{UpdateArgs, RenderArgs, Key};
+use opengl_graphics::GlGraphics;
+use graphics::{Context, Transformed};
+
+use graphics::{line_from_to, rectangle, text};
+use graphics::character::CharacterCache;
+
+// Font imports
+use opengl_graphics::GlyphCache;
+use opengl_graphics::TextureSettings;
+
+use std::collections::VecDeque;
+use std::f64::consts::SQRT_2;
+use std::time::Instant;
+
+use crate::audio::AnalyzedAudio;
+use crate::user_interface::util::find_font;
+
+// How many of the previous frames are still glowing
+const AFTERGLOW_FRAMES: usize = 8;
+// At most this many points are drawn per frame, the rest is skipped
+const MAX_POINTS: usize = 512;
+// The gain never goes beyond this, so noise doesn't fill the scope
+const MAX_GAIN: f64 = 20.0;
+
+/**
+ * Each renderer consists of three things. First, the struct defining its
+ * state. Secondly, an impl that defines the specific methods of the struct
+ * that won't be called by the application. And third, the trait implementation
+ * which defines all methods that are necessary as the application expects them.
+ */
+pub struct Goniometer {
+  width: u32,
+  height: u32,
+  font: GlyphCache<'static>,
+  // The points of the last frames, the newest one first. They are stored
+  // unscaled, so the afterglow follows the gain.
+  afterglow: VecDeque<Vec<[f64; 2]>>,
+  captured_at: Option<Instant>, // Tells the frame in the afterglow apart from the next one
+  level: f64, // The slowly falling peak, which determines the gain
+  correlation: f64 // The displayed correlation, which follows the analyzed one
+}
+
+impl Goniometer {
+  pub fn create () -> Self {
+    Self {
+      width: 200,
+      height: 200,
+      font: GlyphCache::new(find_font().unwrap(), (), TextureSettings::new()).unwrap(),
+      afterglow: VecDeque::with_capacity(AFTERGLOW_FRAMES),
+      captured_at: None,
+      level: 1.0,
+      correlation: 0.0
+    }
+  }
+
+  fn draw_text (&mut self, content: &str, size: u32, position: [f64; 2], color: [f32; 4], context: Context, gl: &mut GlGraphics) {
+    let width = self.font.width(size, content).unwrap();
+    // The position is the center of the text
+    text::Text::new_color(color, size).draw(
+      content,
+      &mut self.font,
+      &context.draw_state,
+      context.transform.trans(position[0] - width / 2.0, position[1] + size as f64 / 2.0),
+      gl
+    ).unwrap();
+  }
+}
+
+impl RendererBase for Goniometer {
+  fn render (&mut self, gl: &mut GlGraphics, context: Context, args: &RenderArgs, audio: &AnalyzedAudio) {
+
+    // Always make sure to use the correct sizes to calculate with
+    self.width = args.draw_size[0];
+    self.height = args.draw_size[1];
+
+    let stereo = &audio.stereo;
+    let text_size = (self.height as f64 * 0.025).clamp(10.0, 14.0) as u32;
+    let radius = (self.width as f64).min(self.height as f64 - 100.0).max(20.0) / 2.0 * 0.9;
+    let center = [self.width as f64 / 2.0, self.height as f64 * 0.1 + radius];
+
+    // We usually render more often than frames are analyzed. Only new frames
+    // join the afterglow, so it lasts as long in audio time at any frame rate.
+    if audio.captured_at != self.captured_at {
+      self.captured_at = audio.captured_at;
+
+      // Rotate the L/R plane by 45°, so mono signals form a vertical line and
+      // signals that are out of phase a horizontal one. The mid and side
+      // signals are exactly that, just scaled down by √2.
+      let step = (stereo.mid.len() / MAX_POINTS).max(1);
+      let points: Vec<[f64; 2]> = stereo.mid.iter().zip(stereo.side.iter()).step_by(step)
+        .map(|(mid, side)| [-*side as f64 * SQRT_2, *mid as f64 * SQRT_2])
+        .collect();
+
+      // Adjust the gain to the loudest recent point, but only slowly go back up
+      let peak = points.iter().map(|[x, y]| x.abs().max(y.abs())).fold(0.0, f64::max);
+      self.level = peak.max(self.level * 0.98).max(1.0 / MAX_GAIN);
+
+      self.afterglow.push_front(points);
+      self.afterglow.truncate(AFTERGLOW_FRAMES);
+    }
+    let gain = radius / self.level;
+
+    // The axes: L and R on the diagonals, M(id) vertical and S(ide) horizontal
+    let axis_color = [0.3, 0.3, 0.3, 1.0];
+    let diagonal = radius / SQRT_2;
+    line_from_to(axis_color, 0.5, [center[0] - diagonal, center[1] - diagonal], [center[0] + diagonal, center[1] + diagonal], context.transform, gl);
+    line_from_to(axis_color, 0.5, [center[0] + diagonal, center[1] - diagonal], [center[0] - diagonal, center[1] + diagonal], context.transform, gl);
+    line_from_to(axis_color, 0.5, [center[0], center[1] - radius], [center[0], center[1] + radius], context.transform, gl);
+    line_from_to(axis_color, 0.5, [center[0] - radius, center[1]], [center[0] + radius, center[1]], context.transform, gl);
+    let label_color = [0.6, 0.6, 0.6, 1.0];
+    let label_distance = diagonal + text_size as f64;
+    self.draw_text("L", text_size, [center[0] - label_distance, center[1] - label_distance], label_color, context, gl);
+    self.draw_text("R", text_size, [center[0] + label_distance, center[1] - label_distance], label_color, context, gl);
+    self.draw_text("M", text_size, [center[0], center[1] - radius - text_size as f64], label_color, context, gl);
+    self.draw_text("S", text_size, [center[0] + radius + text_size as f64, center[1]], label_color, context, gl);
+
+    // Draw the oldest frames first, so the newest points end up on top
+    for (age, points) in self.afterglow.iter().enumerate().rev() {
+      let alpha = 1.0 - age as f32 / AFTERGLOW_FRAMES as f32;
+      let color = [0.3, 1.0, 0.5, alpha * alpha];
+      for [x, y] in points.iter() {
+        let x = center[0] + (x * gain).clamp(-radius, radius);
+        let y = center[1] - (y * gain).clamp(-radius, radius);
+        rectangle(color, [x - 1.0, y - 1.0, 2.0, 2.0], context.transform, gl);
+      }
+    }
+
+    // The correlation meter runs from -1 (left) to +1 (right)
+    self.correlation += (stereo.correlation as f64 - self.correlation) * 0.2;
+    let bar_width = radius * 2.0;
+    let bar_top = center[1] + radius + text_size as f64 * 2.0;
+    let bar_height = 10.0;
+    let bar_left = center[0] - radius;
+    rectangle([0.15, 0.15, 0.15, 1.0], [bar_left, bar_top, bar_width, bar_height], context.transform, gl);
+    let value_x = center[0] + self.correlation * radius;
+    let bar_color = if self.correlation < 0.0 { [1.0, 0.2, 0.2, 1.0] } else { [0.2, 0.8, 0.3, 1.0] };
+    rectangle(bar_color, [center[0].min(value_x), bar_top, (value_x - center[0]).abs(), bar_height], context.transform, gl);
+    rectangle([1.0, 1.0, 1.0, 1.0], [value_x - 1.0, bar_top - 2.0, 2.0, bar_height + 4.0], context.transform, gl);
+    let scale_y = bar_top + bar_height + text_size as f64;
+    self.draw_text("-1", text_size, [bar_left, scale_y], label_color, context, gl);
+    self.draw_text("0", text_size, [center[0], scale_y], label_color, context, gl);
+    self.draw_text("+1", text_size, [bar_left + bar_width, scale_y], label_color, context, gl);
+
+    // And the numbers below
+    let balance = if stereo.balance.abs() < 0.005 {
+      String::from("center")
+    } else {
+      format!("{:.0} % {}", stereo.balance.abs() * 100.0, if stereo.balance < 0.0 { "left" } else { "right" })
+    };
+    let summary = format!("Correlation: {:+.2}, balance: {}, width: {:.2}", stereo.correlation, balance, stereo.width);
+    self.draw_text(&summary, text_size, [center[0], scale_y + text_size as f64 * 2.0], [1.0, 1.0, 1.0, 1.0], context, gl);
+  }
+
+  fn update (&mut self, _args: &UpdateArgs) {
+    // Everything happens during rendering
+  }
+
+  fn on_cursor_movement (&mut self, _x: f64, _y: f64) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_cursor_state (&mut self, _is_over_window: bool) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_click (&mut self) {
+    // Don't react to anything
+  }
+
+  fn on_keypress (&mut self, _key: Key) {
+    // Stoic renderer, I tell you
+  }
+}
@@ -0,0 +1,177 @@
+use crate::traits::RendererBase;
+use piston::input::{UpdateArgs, RenderArgs, Key};
+use opengl_graphics::GlGraphics;
+use graphics::{Context, Transformed};
+
+use graphics::{rectangle, text};
+use graphics::character::CharacterCache;
+
+// Font imports
+use opengl_graphics::GlyphCache;
+use opengl_graphics::TextureSettings;
+
+use crate::audio::AnalyzedAudio;
+use crate::audio::smoothing::Smoother;
+use crate::audio::decibel::DecibelScale;
+use crate::user_interface::util::find_font;
+
+// A tick (with label) every this many dB
+const TICK_SPACING: f32 = 6.0;
+// Levels above this are drawn in red
+const WARNING_LEVEL: f32 = -1.0;
+
+/**
+ * Each renderer consists of three things. First, the struct defining its
+ * state. Secondly, an impl that defines the specific methods of the struct
+ * that won't be called by the application. And third, the trait implementation
+ * which defines all methods that are necessary as the application expects them.
+ */
+pub struct Meter {
+  width: u32,
+  height: u32,
+  font: GlyphCache<'static>,
+  // Applies the ballistics selected in the UI to all bars
+  smoother: Smoother,
+  elapsed: f64 // Seconds since the last render
+}
+
+impl Meter {
+  pub fn create () -> Self {
+    Self {
+      width: 200,
+      height: 200,
+      font: GlyphCache::new(find_font().unwrap(), (), TextureSettings::new()).unwrap(),
+      smoother: Smoother::create(),
+      elapsed: 0.0
+    }
+  }
+
+  /// Draws one vertical bar with its peak cap. The level and peak run from
+  /// 0.0 (bottom) to 1.0 (top).
+  fn draw_bar (rect: [f64; 4], level: f64, peak: f64, color: [f32; 4], context: Context, gl: &mut GlGraphics) {
+    rectangle([0.15, 0.15, 0.15, 1.0], rect, context.transform, gl);
+    let height = level * rect[3];
+    rectangle(color, [rect[0], rect[1] + rect[3] - height, rect[2], height], context.transform, gl);
+    let peak_y = rect[1] + rect[3] - peak * rect[3];
+    rectangle([1.0, 1.0, 1.0, 1.0], [rect[0], peak_y, rect[2], 2.0], context.transform, gl);
+  }
+
+  fn draw_text (&mut self, content: &str, size: u32, position: [f64; 2], context: Context, gl: &mut GlGraphics) {
+    text::Text::new_color([1.0, 1.0, 1.0, 1.0], size).draw(
+      content,
+      &mut self.font,
+      &context.draw_state,
+      context.transform.trans(position[0], position[1]),
+      gl
+    ).unwrap();
+  }
+}
+
+/// Formats a level for the readouts, which may well be negative infinity
+fn format_level (level: f32) -> String {
+  if level.is_finite() {
+    format!("{:.1}", level)
+  } else {
+    String::from("-inf")
+  }
+}
+
+impl RendererBase for Meter {
+  fn render (&mut self, gl: &mut GlGraphics, context: Context, args: &RenderArgs, audio: &AnalyzedAudio) {
+
+    // Always make sure to use the correct sizes to calculate with
+    self.width = args.draw_size[0];
+    self.height = args.draw_size[1];
+
+    let loudness = &audio.loudness;
+    let channels = loudness.rms.len();
+
+    // All bars are smoothed at once: first the RMS, then the sample peaks of
+    // all channels, then the momentary and short-term loudness. The smoother
+    // works on magnitudes, so we convert the levels back.
+    let mut levels: Vec<f32> = Vec::new();
+    levels.extend(loudness.rms.iter());
+    levels.extend(loudness.sample_peak.iter());
+    levels.push(loudness.momentary);
+    levels.push(loudness.short_term);
+    let magnitudes: Vec<f32> = levels.iter().map(|level| 10_f32.powf(level / 20.0)).collect();
+    self.smoother.process(&magnitudes, self.elapsed.max(0.001) as f32, &audio.ballistics);
+    self.elapsed = 0.0;
+    let values = self.smoother.values();
+    let peaks = self.smoother.peaks();
+
+    let scale = audio.decibel_scale;
+    let text_size = (self.height as f64 * 0.03).clamp(10.0, 16.0) as u32;
+    let top = self.height as f64 * 0.1;
+    let bar_height = self.height as f64 * 0.6;
+    // Each channel takes up about 3.5 bar widths, the loudness bars 5 more
+    let bar_width = (self.width as f64 * 0.8 / (channels as f64 * 3.5 + 5.0)).min(self.width as f64 * 0.05).min(40.0);
+    let gap = bar_width * 0.5;
+
+    // The scale on the left
+    let scale_x = self.width as f64 * 0.1;
+    let mut tick = scale.ceiling;
+    while tick >= scale.floor {
+      let y = top + bar_height - scale.normalize(10_f32.powf(tick / 20.0)) as f64 * bar_height;
+      rectangle([0.5, 0.5, 0.5, 1.0], [scale_x, y, 8.0, 1.0], context.transform, gl);
+      let label = format!("{}", tick);
+      let label_width = self.font.width(text_size, &label).unwrap();
+      self.draw_text(&label, text_size, [scale_x - label_width - 4.0, y + text_size as f64 / 2.0], context, gl);
+      tick -= TICK_SPACING;
+    }
+
+    // One RMS and one peak bar per channel ...
+    let mut x = scale_x + 20.0;
+    for channel in 0..channels {
+      for (kind, idx) in [("RMS", channel), ("Peak", channels + channel)].iter() {
+        let color = if DecibelScale::to_decibels(values[*idx]) > WARNING_LEVEL { [1.0, 0.2, 0.2, 1.0] } else { [0.2, 0.8, 0.3, 1.0] };
+        Self::draw_bar([x, top, bar_width, bar_height], scale.normalize(values[*idx]) as f64, scale.normalize(peaks[*idx]) as f64, color, context, gl);
+        self.draw_text(kind, text_size, [x, top + bar_height + text_size as f64 + 4.0], context, gl);
+        x += bar_width + gap;
+      }
+      self.draw_text(&format!("Ch. {}", channel + 1), text_size, [x - 2.0 * (bar_width + gap), top - 8.0], context, gl);
+      x += gap;
+    }
+
+    // ... and the loudness bars
+    x += gap * 2.0;
+    for (kind, idx) in [("M", channels * 2), ("S", channels * 2 + 1)].iter() {
+      Self::draw_bar([x, top, bar_width, bar_height], scale.normalize(values[*idx]) as f64, scale.normalize(peaks[*idx]) as f64, [0.3, 0.6, 1.0, 1.0], context, gl);
+      self.draw_text(kind, text_size, [x, top + bar_height + text_size as f64 + 4.0], context, gl);
+      x += bar_width + gap;
+    }
+
+    // The readouts below the bars
+    let true_peaks: Vec<String> = loudness.true_peak.iter().map(|peak| format_level(*peak)).collect();
+    let lines = [
+      format!("Momentary: {} LUFS, short-term: {} LUFS", format_level(loudness.momentary), format_level(loudness.short_term)),
+      format!("Integrated: {} LUFS, loudness range: {:.1} LU", format_level(loudness.integrated), loudness.loudness_range),
+      format!("True peak: {} dBTP (max. {} dBTP)", true_peaks.join(" / "), format_level(loudness.max_true_peak))
+    ];
+    let mut y = top + bar_height + text_size as f64 * 3.0 + 8.0;
+    for line in lines.iter() {
+      self.draw_text(line, text_size, [scale_x, y], context, gl);
+      y += text_size as f64 * 1.5;
+    }
+  }
+
+  fn update (&mut self, args: &UpdateArgs) {
+    self.elapsed += args.dt;
+  }
+
+  fn on_cursor_movement (&mut self, _x: f64, _y: f64) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_cursor_state (&mut self, _is_over_window: bool) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_click (&mut self) {
+    // Don't react to anything
+  }
+
+  fn on_keypress (&mut self, _key: Key) {
+    // Stoic renderer, I tell you
+  }
+}
@@ -1,7 +1,15 @@
 mod frequalizer;
 mod eye_of_hal;
 mod stereo_tree;
+mod tuner;
+mod chroma_wheel;
+mod meter;
+mod goniometer;
 
 pub use frequalizer::Frequalizer;
 pub use eye_of_hal::EyeOfHAL;
 pub use stereo_tree::StereoTree;
+pub use tuner::Tuner;
+pub use chroma_wheel::ChromaWheel;
+pub use meter::Meter;
+pub use goniometer::Goniometer;
@@ -80,60 +80,41 @@ impl RendererBase for StereoTree {
     self.width = args.draw_size[0];
     self.height = args.draw_size[1];
     let center: f64 = self.width as f64 / 2.0;
-    let center_bar_width: f64 = self.width as f64 * 0.001; // 0,1 %
+    // 0,1 %, but it pulses to the beat
+    let center_bar_width: f64 = self.width as f64 * (0.001 + 0.004 * (1.0 - audio.beat_phase as f64).powi(4));
     let max_width: f64 = self.width as f64 / 3.0;
 
-    if audio.frequency[0].is_empty() {
+    // The routing decides which channels end up on the left and right
+    let spectrum = audio.routed(&audio.smoothed_spectrum);
+    let peaks = audio.routed(&audio.spectrum_peaks);
+    let amplitude = audio.routed(&audio.amplitude);
+
+    if spectrum[0].is_empty() {
       return; // Nothing to render
     }
 
     // Before we are done rendering, display the center bar
     rectangle([1.0, 1.0, 1.0, 1.0], [center - center_bar_width / 2.0, 0.0, center_bar_width, self.height as f64], context.transform, gl);
 
-    // We don't want 22kHz displayed as this would be WAY too unreasonable,
-    // so we need to find the correct cutoff frequency for which to perform
-    // some crude lowpass filter.
-    // f / bin = i
-    let mut cutoff_frequency = (20_000.0 / audio.bin_frequency).floor() as usize;
-    if cutoff_frequency > audio.frequency[0].len() {
-      cutoff_frequency = audio.frequency[0].len();
-    }
+    // We display the selected view of the spectrum. We only need to skip the
+    // bands beyond the Nyquist frequency, as they will always be empty.
+    let nyquist = audio.sample_rate as f32 / 2.0;
+    let band_count = audio.spectrum_frequencies().iter().filter(|frequency| **frequency < nyquist).count();
+
+    // The colors follow the timbre: dark sounds are blue, bright ones red
+    let target_hue = (1.0 - audio.features.brightness()) * 240.0;
+    self.hue += (target_hue - self.hue) * 0.05;
 
     // Determine how high the frequency bars may be at the most
-    // let rectangle_width: f64 = (self.width as f64 / 2.0) / cutoff_frequency as f64;
-    let frequency_bar_height: f64 = self.height as f64 / cutoff_frequency as f64;
-    let amplitude_bar_height: f64 = self.height as f64 / audio.amplitude[0].len() as f64;
-
-    // Second, calculate the maximum frequency amplitude (= max width)
-    let mut max_frequency_amp = 0.0;
-    for sample in audio.frequency[0][0..cutoff_frequency].iter() {
-      if sample.abs() as f64 > max_frequency_amp {
-        max_frequency_amp = sample.abs() as f64;
-      }
-    }
-    // Third, calculate the maximum volume amplitude (= max width)
-    let mut max_volume_amp = 0.0;
-    for sample in audio.amplitude[0].iter() {
-      if sample.abs() as f64 > max_volume_amp {
-        max_volume_amp = sample.abs() as f64;
-      }
-    }
+    let frequency_bar_height: f64 = self.height as f64 / band_count as f64;
+    let amplitude_bar_height: f64 = self.height as f64 / amplitude[0].len() as f64;
+    let peak_width: f64 = (self.width as f64 * 0.002).max(1.0);
 
     // Display the bars! First the amplitude (as grey underlying bars) ...
-    for i in 0..audio.amplitude[0].len() {
-      let amplitude_left = audio.amplitude[0][i] as f64;
-      let amplitude_right = audio.amplitude[1][i] as f64;
-
-      let mut width_left: f64 = amplitude_left / max_volume_amp; // val from 0.0-1.0
-      let mut width_right: f64 = amplitude_right / max_volume_amp;
-
-      // Normalize
-      if width_left > 1.0 {
-        width_left = 1.0;
-      }
-      if width_right > 1.0 {
-        width_right = 1.0;
-      }
+    for (i, (left, right)) in amplitude[0].iter().zip(amplitude[1].iter()).enumerate() {
+      // The level on the dB scale, from 0.0-1.0
+      let mut width_left = audio.decibel_scale.normalize(*left) as f64;
+      let mut width_right = audio.decibel_scale.normalize(*right) as f64;
 
       // Transform to final values
       width_left *= max_width;
@@ -145,29 +126,20 @@ impl RendererBase for StereoTree {
       let posx_right = center; // Always begins in the center
       let posy_right = posy_left;
 
-      let opacity = self.hue / 360.0;
+      // Noisy sounds let the waveform shine through
+      let opacity = 0.2 + 0.8 * audio.features.flatness;
 
       rectangle([0.3, 0.3, 0.3, opacity], [posx_left, posy_left, width_left, amplitude_bar_height], context.transform, gl);
       rectangle([0.3, 0.3, 0.3, opacity], [posx_right, posy_right, width_right, amplitude_bar_height], context.transform, gl);
     }
 
     // ... and then a colourful frequency on top
-    for i in 0..cutoff_frequency {
+    for i in 0..band_count {
       let col = Self::hue_to_rgb(self.hue + i as f32 % 360.0);
 
-      let frequency_left = audio.frequency[0][i] as f64;
-      let frequency_right = audio.frequency[1][i] as f64;
-
-      let mut width_left: f64 = frequency_left.abs() / max_frequency_amp; // val from 0.0-1.0
-      let mut width_right: f64 = frequency_right.abs() / max_frequency_amp;
-
-      // Normalize
-      if width_left > 1.0 {
-        width_left = 1.0;
-      }
-      if width_right > 1.0 {
-        width_right = 1.0;
-      }
+      // The level on the dB scale, from 0.0-1.0
+      let mut width_left = audio.decibel_scale.normalize(spectrum[0][i]) as f64;
+      let mut width_right = audio.decibel_scale.normalize(spectrum[1][i]) as f64;
 
       // Transform to final values
       width_left *= max_width;
@@ -181,16 +153,19 @@ impl RendererBase for StereoTree {
 
       rectangle(col, [posx_left, posy_left, width_left, frequency_bar_height], context.transform, gl);
       rectangle(col, [posx_right, posy_right, width_right, frequency_bar_height], context.transform, gl);
+
+      // The peak caps slowly fall back towards the center
+      let peak_left = audio.decibel_scale.normalize(peaks[0][i]) as f64 * max_width;
+      let peak_right = audio.decibel_scale.normalize(peaks[1][i]) as f64 * max_width;
+      rectangle([1.0, 1.0, 1.0, 1.0], [center - peak_left - peak_width, posy_left, peak_width, frequency_bar_height], context.transform, gl);
+      rectangle([1.0, 1.0, 1.0, 1.0], [center + peak_right, posy_right, peak_width, frequency_bar_height], context.transform, gl);
     }
 
     // And done!
   }
 
   fn update (&mut self, _args: &UpdateArgs) {
-    self.hue += 0.1;
-    if self.hue > 360.0 {
-      self.hue = 0.0;
-    }
+    // Everything happens during rendering
   }
 
   fn on_cursor_movement (&mut self, _x: f64, _y: f64) {
@@ -0,0 +1,135 @@
+use crate::traits::RendererBase;
+use piston::input::{UpdateArgs, RenderArgs, Key};
+use opengl_graphics::GlGraphics;
+use graphics::{Context, Transformed};
+
+use graphics::{rectangle, text};
+use graphics::character::CharacterCache;
+
+// Font imports
+use opengl_graphics::GlyphCache;
+use opengl_graphics::TextureSettings;
+
+use crate::audio::AnalyzedAudio;
+use crate::user_interface::util::find_font;
+
+// Within this many cents, the note counts as in tune
+const IN_TUNE: f32 = 5.0;
+
+/**
+ * Each renderer consists of three things. First, the struct defining its
+ * state. Secondly, an impl that defines the specific methods of the struct
+ * that won't be called by the application. And third, the trait implementation
+ * which defines all methods that are necessary as the application expects them.
+ */
+pub struct Tuner {
+  width: u32,
+  height: u32,
+  font: GlyphCache<'static>,
+  needle: f32, // The displayed deviation in cents, which follows the detected one
+  note: String, // The last detected note, e.g. "A4"
+  info: String, // The frequency and deviation of the last detected note
+  opacity: f32 // Fades out once there is no pitch anymore
+}
+
+impl Tuner {
+  pub fn create () -> Self {
+    Self {
+      width: 200,
+      height: 200,
+      font: GlyphCache::new(find_font().unwrap(), (), TextureSettings::new()).unwrap(),
+      needle: 0.0,
+      note: String::new(),
+      info: String::new(),
+      opacity: 0.0
+    }
+  }
+
+  /// Draws a text horizontally centered around x, with its baseline at y
+  fn draw_centered_text (&mut self, content: &str, size: u32, color: [f32; 4], position: [f64; 2], context: Context, gl: &mut GlGraphics) {
+    let width = self.font.width(size, content).unwrap();
+    text::Text::new_color(color, size).draw(
+      content,
+      &mut self.font,
+      &context.draw_state,
+      context.transform.trans(position[0] - width / 2.0, position[1]),
+      gl
+    ).unwrap();
+  }
+}
+
+impl RendererBase for Tuner {
+  fn render (&mut self, gl: &mut GlGraphics, context: Context, args: &RenderArgs, audio: &AnalyzedAudio) {
+
+    // Always make sure to use the correct sizes to calculate with
+    self.width = args.draw_size[0];
+    self.height = args.draw_size[1];
+
+    let center_x = self.width as f64 / 2.0;
+    let center_y = self.height as f64 / 2.0;
+    let scale_width = self.width as f64 * 0.8;
+    let note_size = (self.height as f64 * 0.25).min(200.0) as u32;
+    let info_size = (note_size / 5).max(10);
+
+    // Keep showing the last note for a moment, which keeps it readable
+    if let Some(pitch) = audio.pitch {
+      self.needle += (pitch.cents - self.needle) * 0.3;
+      self.note = format!("{}{}", pitch.note, pitch.octave);
+      self.info = format!("{:.1} Hz, {:+.0} cents (clarity: {:.0} %)", pitch.frequency, pitch.cents, pitch.clarity * 100.0);
+      self.opacity = 1.0;
+    } else {
+      self.opacity = (self.opacity - 0.02).max(0.0);
+    }
+
+    // The scale from -50 to +50 cents, with a tick every ten cents
+    let grey = [0.5, 0.5, 0.5, 1.0];
+    for tick in -5..=5 {
+      let x = center_x + tick as f64 / 10.0 * scale_width;
+      let tick_height = if tick == 0 { 40.0 } else { 20.0 };
+      rectangle(grey, [x - 1.0, center_y - tick_height / 2.0, 2.0, tick_height], context.transform, gl);
+    }
+
+    let in_tune = self.needle.abs() <= IN_TUNE;
+    let color = if in_tune {
+      [0.2, 1.0, 0.2, self.opacity]
+    } else {
+      [1.0, 0.4, 0.2, self.opacity]
+    };
+
+    // The needle
+    let needle_x = center_x + self.needle as f64 / 100.0 * scale_width;
+    rectangle(color, [needle_x - 3.0, center_y - 40.0, 6.0, 80.0], context.transform, gl);
+
+    if self.opacity > 0.0 {
+      // The note with its octave above the scale ...
+      let note = self.note.clone();
+      self.draw_centered_text(&note, note_size, color, [center_x, center_y - 60.0], context, gl);
+
+      // ... and the exact values below it
+      let info = self.info.clone();
+      self.draw_centered_text(&info, info_size, [1.0, 1.0, 1.0, self.opacity], [center_x, center_y + 60.0 + info_size as f64], context, gl);
+    } else {
+      self.draw_centered_text("No pitch detected", info_size, grey, [center_x, center_y + 60.0 + info_size as f64], context, gl);
+    }
+  }
+
+  fn update (&mut self, _args: &UpdateArgs) {
+    // Everything happens during rendering
+  }
+
+  fn on_cursor_movement (&mut self, _x: f64, _y: f64) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_cursor_state (&mut self, _is_over_window: bool) {
+    // This renderer does not react to mouse events :(
+  }
+
+  fn on_click (&mut self) {
+    // Don't react to anything
+  }
+
+  fn on_keypress (&mut self, _key: Key) {
+    // Stoic renderer, I tell you
+  }
+}
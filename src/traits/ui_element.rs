@@ -6,11 +6,24 @@ use piston::input::{
 
 use opengl_graphics::GlGraphics;
 use graphics::Context;
+use cpal::StreamConfig;
 
 pub enum UIEvent {
   RequestChangeAudioDevice(usize),
   Selection(usize, usize),
-  RequestChangeRenderer(usize)
+  RequestChangeRenderer(usize),
+  RequestChangeWindow(usize), // Index into WindowFunction::all()
+  RequestChangeFftSize(usize), // Index into FFT_SIZES
+  RequestChangeOverlap(usize), // Index into OVERLAPS
+  RequestChangeZeroPadding(bool),
+  RequestChangeBandLayout(usize), // Index into BandLayout::all()
+  RequestChangeDecibelRange(usize), // Index into DECIBEL_RANGES
+  RequestChangeSmoothing(usize), // Index into Ballistics::all()
+  RequestChangeReferenceTuning(usize), // Index into REFERENCE_TUNINGS
+  RequestChangeView(usize), // Index into SpectrumView::all()
+  RequestChangeChannelRouting(usize), // Index into ChannelRouting::all() for the current channels
+  RequestChangeStreamConfig(StreamConfig), // Reopens the current input device with this config
+  RequestChangeHost(usize) // Index into cpal::available_hosts()
 }
 
 pub trait UIElement {
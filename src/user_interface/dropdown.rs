@@ -26,7 +26,6 @@ pub struct UIDropdown {
   rect: [f64; 4],
   item_height: f64,
   padding: f64,
-  draw_from_bottom: bool,
   font: graphics::glyph_cache::rusttype::GlyphCache<'static, (), opengl_graphics::Texture>
 }
 
@@ -74,7 +73,6 @@ impl UIDropdown {
       position,
       padding,
       item_height,
-      draw_from_bottom: draw_up,
       font
     }
   }
@@ -125,19 +123,8 @@ impl UIElement for UIDropdown {
       return None;
     }
 
-    let mut i = -1.0;
-    for (_idx, _item) in self.items.iter() {
-      // Draw em'
-      i += 1.0;
-      let item_y = self.rect[1] + i * self.item_height;
-      if cursor_in_rect(self.position, [self.rect[0], item_y, self.rect[2], self.item_height]) {
-        // Cursor is within this element
-        // Following event needs to be emitted:
-        return Some(UIEvent::Selection(i as usize, self.id));
-      }
-    }
-
-    None // Fallback
+    // Emit the value stored with the item, which isn't necessarily its position
+    item_at(&self.items, self.rect, self.item_height, self.position).map(|idx| UIEvent::Selection(idx, self.id))
   }
 
   // /// Called when a key on the keyboard has been pressed
@@ -145,3 +132,31 @@ impl UIElement for UIDropdown {
     // TODO: Handle up/down arrows when this thing has focus
   }
 }
+
+/// Returns the value of the item under the cursor, if any
+fn item_at (items: &[(usize, String)], rect: [f64; 4], item_height: f64, position: [f64; 2]) -> Option<usize> {
+  for (i, (idx, _item)) in items.iter().enumerate() {
+    let item_y = rect[1] + i as f64 * item_height;
+    if cursor_in_rect(position, [rect[0], item_y, rect[2], item_height]) {
+      return Some(*idx);
+    }
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn selects_the_value_of_an_item () {
+    // E.g. the channel counts of a device
+    let items = vec![(1, String::from("1")), (2, String::from("2"))];
+    let rect = [10.0, 100.0, 50.0, 40.0];
+    assert_eq!(item_at(&items, rect, 20.0, [20.0, 110.0]), Some(1));
+    assert_eq!(item_at(&items, rect, 20.0, [20.0, 130.0]), Some(2));
+    assert_eq!(item_at(&items, rect, 20.0, [20.0, 150.0]), None);
+    assert_eq!(item_at(&items, rect, 20.0, [5.0, 110.0]), None);
+  }
+}
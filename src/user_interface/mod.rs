@@ -22,27 +22,53 @@ use opengl_graphics::TextureSettings;
 
 // Needed to satisfy the trait
 use crate::audio::AnalyzedAudio;
+use crate::audio::window::WindowFunction;
+use crate::audio::frames::{FFT_SIZES, OVERLAPS};
+use crate::audio::bands::BandLayout;
+use crate::audio::decibel::{DecibelScale, DECIBEL_RANGES};
+use crate::audio::smoothing::Ballistics;
+use crate::audio::chroma::REFERENCE_TUNINGS;
+use crate::audio::view::SpectrumView;
+use crate::audio::routing::ChannelRouting;
+use cpal::{BufferSize, HostId, SampleRate, StreamConfig};
 
 // Import UI elements
 mod dropdown;
 use dropdown::UIDropdown;
-mod util;
+pub mod util;
 
-use util::{cursor_in_rect, format_number, find_font};
+use util::{cursor_in_rect, flow_layout, format_number, format_buffer_size, find_font};
 
 // Needed for the timeout
 use std::time;
 
-// Needed to resolve the asset path
-use std::env::current_dir;
-
-use std::path::Path;
-
 // Necessary to retrieve a list of available input devices.
 use crate::audio::util::{fetch_devices, AudioDevice};
 
 static AUDIO_IO_ID: usize = 1;
 static RENDERER_ID: usize = 2;
+static WINDOW_ID: usize = 3;
+static FFT_SIZE_ID: usize = 4;
+static OVERLAP_ID: usize = 5;
+static BANDS_ID: usize = 6;
+static DECIBEL_RANGE_ID: usize = 7;
+static SMOOTHING_ID: usize = 8;
+static TUNING_ID: usize = 9;
+static VIEW_ID: usize = 10;
+static ROUTING_ID: usize = 11;
+static SAMPLE_RATE_ID: usize = 12;
+static CHANNELS_ID: usize = 13;
+static DEVICE_BUFFER_ID: usize = 14;
+static HOST_ID: usize = 15;
+
+// How long an audio error stays on screen, in seconds
+const ERROR_DISPLAY_TIME: u64 = 5;
+
+// The space the buttons keep to each other and to the edges of the overlay
+const BUTTON_SPACING: f64 = 20.0;
+const OVERLAY_PADDING: f64 = 10.0;
+// The width of the small level display after the last button
+const LEVEL_DISPLAY_WIDTH: f64 = 50.0;
 
 pub struct UI<'a> {
   width: u32,
@@ -57,6 +83,8 @@ pub struct UI<'a> {
   target_opacity: f64,
   ui_font: graphics::glyph_cache::rusttype::GlyphCache<'a, (), opengl_graphics::Texture>,
   // Displayable settings
+  available_hosts: Vec<HostId>,
+  selected_host: HostId,
   available_devices: Vec<AudioDevice>,
   available_renderers: Vec<String>,
   ui_elements: Vec<Box<dyn UIElement>>,
@@ -66,22 +94,35 @@ pub struct UI<'a> {
   device_info: String,
   base_font_size: f64,
   font_path: String,
+  host_selector_button_rect: [f64; 4],
   input_selector_button_rect: [f64; 4],
+  sample_rate_selector_button_rect: [f64; 4],
+  channels_selector_button_rect: [f64; 4],
+  device_buffer_selector_button_rect: [f64; 4],
   renderer_selector_button_rect: [f64; 4],
-  input_selector_index: i32,
-  renderer_selector_index: i32,
+  window_selector_button_rect: [f64; 4],
+  fft_size_selector_button_rect: [f64; 4],
+  overlap_selector_button_rect: [f64; 4],
+  padding_button_rect: [f64; 4],
+  bands_selector_button_rect: [f64; 4],
+  decibel_range_selector_button_rect: [f64; 4],
+  smoothing_selector_button_rect: [f64; 4],
+  tuning_selector_button_rect: [f64; 4],
+  view_selector_button_rect: [f64; 4],
+  routing_selector_button_rect: [f64; 4],
+  zero_padding: bool,
+  channels: usize, // The channels of the source, which determine the routings we offer
+  sample_rate: u32,
+  device_buffer_size: BufferSize, // The buffer size the input device has been opened with
+  input_lost: bool, // True while we're listening to nothing
+  audio_error: Option<(String, time::Instant)>, // The last error of the audio system, and when it happened
+  open_dropdown: Option<usize>, // The ID of the currently open dropdown
   min_amp: f32,
   max_amp: f32
 }
 
 impl UI<'static> {
   pub fn create () -> Self {
-    let font_path = find_font();
-    if let Err(e) = font_path {
-      use std::io::Write;
-      let mut file = std::fs::File::create("/Users/hendrik/Desktop/log.txt").unwrap();
-      file.write_all(b"Could not find the font path!").unwrap();
-    }
     let glyph_cache = GlyphCache::new(find_font().unwrap(), (), TextureSettings::new()).unwrap();
 
     Self {
@@ -99,7 +140,9 @@ impl UI<'static> {
       menu_display_time: 2,
       ui_font: glyph_cache,
       // Information
-      available_devices: fetch_devices(),
+      available_hosts: cpal::available_hosts(),
+      selected_host: cpal::default_host().id(),
+      available_devices: Vec::new(), // See selected_host_changed
       available_renderers: Vec::new(),
       selected_device: 0,
       selected_renderer: 0,
@@ -108,10 +151,29 @@ impl UI<'static> {
       font_path: find_font().unwrap(),
       ui_elements: Vec::new(),
       base_font_size: 12.0,
+      host_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
       input_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      sample_rate_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      channels_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      device_buffer_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
       renderer_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
-      input_selector_index: -1,
-      renderer_selector_index: -1,
+      window_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      fft_size_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      overlap_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      padding_button_rect: [0.0, 0.0, 0.0, 0.0],
+      bands_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      decibel_range_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      smoothing_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      tuning_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      view_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      routing_selector_button_rect: [0.0, 0.0, 0.0, 0.0],
+      zero_padding: false,
+      channels: 0,
+      sample_rate: 0,
+      device_buffer_size: BufferSize::Default,
+      input_lost: false,
+      audio_error: None,
+      open_dropdown: None,
       min_amp: 0.0,
       max_amp: 0.0
     }
@@ -120,6 +182,67 @@ impl UI<'static> {
   // Helper and utility functions
   pub fn selected_audio_device_changed (&mut self, idx: usize) {
     self.selected_device = idx;
+    self.device_buffer_size = BufferSize::Default; // Devices are opened with the default buffer size
+    self.input_lost = false;
+    self.audio_error = None;
+  }
+
+  /// Switches to the devices of another audio host
+  pub fn selected_host_changed (&mut self, host: HostId) {
+    self.selected_host = host;
+    self.available_devices = fetch_devices(host).unwrap_or_else(|e| {
+      // The audio system will tell us as well, so just show nothing
+      println!("ERROR: {}", e);
+      Vec::new()
+    });
+  }
+
+  /// Replaces the list of devices, e.g. after one has been plugged in. The
+  /// indices may have shifted, so the selected device is looked up by name.
+  pub fn available_devices_changed (&mut self, devices: Vec<AudioDevice>) {
+    if let Some(selected) = self.available_devices.get(self.selected_device) {
+      // If it's gone, nothing is selected until the audio system tells us
+      // what it has switched to
+      self.selected_device = devices.iter().position(|device| device.name == selected.name).unwrap_or(devices.len());
+    }
+    self.available_devices = devices;
+  }
+
+  /// Displays an error of the audio system for a few seconds
+  pub fn audio_error_occurred (&mut self, message: String) {
+    self.audio_error = Some((message, time::Instant::now()));
+  }
+
+  /// Switches to the "no input" state until another device is selected
+  pub fn input_lost (&mut self) {
+    self.input_lost = true;
+  }
+
+  /// Draws the "no input" state and the last audio error in the center of
+  /// the window, regardless of whether the rest of the UI is visible
+  fn draw_audio_status (&mut self, gl: &mut GlGraphics, context: Context) {
+    let mut lines = Vec::new();
+    if self.input_lost {
+      lines.push(String::from("No input. Move the mouse to select a different source."));
+    }
+    if let Some((message, time)) = self.audio_error.as_ref() {
+      // While we have no input, the reason stays on screen
+      if self.input_lost || time.elapsed() < time::Duration::from_secs(ERROR_DISPLAY_TIME) {
+        lines.push(message.clone());
+      }
+    }
+
+    let font_size = 14;
+    for (i, line) in lines.iter().enumerate() {
+      let width = self.ui_font.width(font_size, line).unwrap();
+      text::Text::new_color([1.0, 0.4, 0.4, 1.0], font_size).draw(
+        line,
+        &mut self.ui_font,
+        &context.draw_state,
+        context.transform.trans((self.width as f64 - width) / 2.0, self.height as f64 / 2.0 + i as f64 * font_size as f64 * 1.5),
+        gl
+      ).unwrap();
+    }
   }
 
   pub fn selected_renderer_changed (&mut self, idx: usize) {
@@ -134,11 +257,34 @@ impl UI<'static> {
     self.available_renderers = rend;
   }
 
+  /// Opens a dropdown with the given items right above a button
+  fn open_dropdown (&mut self, id: usize, items: Vec<(usize, String)>, button_rect: [f64; 4]) {
+    self.ui_elements.push(
+      Box::new(
+        UIDropdown::create(
+          id,
+          items, true,
+          [button_rect[0], button_rect[1]],
+          button_rect[2],
+          self.base_font_size,
+          self.font_path.clone()
+        )
+      )
+    );
+    self.open_dropdown = Some(id);
+  }
+
+  /// The width a text button will take up
+  fn text_button_width (&mut self, text: &str) -> f64 {
+    let padding = 5.0;
+    self.ui_font.width(self.base_font_size as u32, text).unwrap() + 2.0 * padding
+  }
+
   /// Draw a text button and return the actual rectangle where it has been drawn
   fn draw_text_button (&mut self, begin_point: [f64; 2], text: String, gl: &mut GlGraphics, context: Context) -> [f64; 4] {
     // Draws a text button with the UIs style
     let padding = 5.0;
-    let real_width = self.ui_font.width(self.base_font_size as u32, text.as_str()).unwrap() + 2.0 * padding;
+    let real_width = self.text_button_width(text.as_str());
     let real_height = self.base_font_size + 2.0 * padding;
 
     let rect = [
@@ -180,24 +326,84 @@ impl UI<'static> {
 
 impl RendererBase for UI<'static> {
   fn render (&mut self, gl: &mut GlGraphics, context: Context, args: &RenderArgs, audio: &AnalyzedAudio) {
-    if self.ui_opacity == 0.0 {
-      return // If the opacity is zero, we don't need to waste resources
-    }
-
     // Window size
     self.width = args.draw_size[0];
     self.height = args.draw_size[1];
 
-    // Overlay size (width is always full)
-    let overlay_top = self.height as f64 * 0.8;
-    let overlay_height = self.height as f64 * 0.2;
+    self.draw_audio_status(gl, context);
+
+    if self.ui_opacity == 0.0 {
+      return // If the opacity is zero, we don't need to waste resources
+    }
 
-    // Font size relative to UI overlay (always three lines high)
-    self.base_font_size = (overlay_height / 3.0 * 0.95).floor();
+    // Font size relative to the default overlay (three lines high, the
+    // overlay grows with the rows of buttons)
+    let min_overlay_height = self.height as f64 * 0.2;
+    self.base_font_size = (min_overlay_height / 3.0 * 0.95).floor();
     if self.base_font_size > 14.0 {
       self.base_font_size = 14.0; // Don't overdo it
     }
 
+    let mut selected_device = String::from("No device selected");
+    // Check if we have a device selected
+    if !self.available_devices.is_empty() && self.selected_device < self.available_devices.len() {
+      selected_device = self.available_devices[self.selected_device].name.clone();
+    }
+
+    if self.input_lost {
+      selected_device = String::from("No input");
+    }
+
+    self.device_info = format!("IN: {}", selected_device);
+    self.channels = audio.channels;
+    self.sample_rate = audio.sample_rate;
+    let overlap = 100.0 - audio.hop_size as f64 / audio.buffer_size.max(1) as f64 * 100.0;
+
+    // The buttons come in three groups, each starting a row of its own: the
+    // audio input, the analysis, and what the renderers display. The order
+    // of the labels needs to match the rects assigned below.
+    let labels = vec![
+      vec![
+        format!("Host: {}", self.selected_host.name()),
+        self.device_info.clone(),
+        format!("Rate: {} Hz", format_number(audio.sample_rate as f64)),
+        format!("Inputs: {}", audio.channels),
+        format!("Device buffer: {}", format_buffer_size(&self.device_buffer_size))
+      ],
+      vec![
+        format!("Window: {}", audio.window.name()),
+        format!("FFT: {}", format_number(audio.buffer_size as f64)),
+        format!("Overlap: {} %", overlap.round()),
+        format!("Padding: {}", if self.zero_padding { "on" } else { "off" }), // A toggle, no dropdown
+        format!("Smoothing: {}", audio.ballistics.name()),
+        format!("A4: {} Hz", audio.reference_tuning)
+      ],
+      vec![
+        format!("Renderer: {}", self.available_renderers[self.selected_renderer].clone()),
+        format!("View: {}", audio.view.name()),
+        format!("Bands: {}", audio.band_layout.name()),
+        format!("Range: {}", audio.decibel_scale.name()),
+        format!("Channels: {}", audio.routing.name())
+      ]
+    ];
+
+    // Wrap the buttons to the window, with the level display after the last one
+    let mut widths: Vec<Vec<f64>> = labels.iter().map(|group| {
+      group.iter().map(|label| self.text_button_width(label)).collect()
+    }).collect();
+    if let Some(last_group) = widths.last_mut() {
+      last_group.push(LEVEL_DISPLAY_WIDTH);
+    }
+    let button_height = self.base_font_size + 10.0;
+    let available_width = self.width as f64 - 2.0 * OVERLAY_PADDING;
+    let (positions, buttons_height) = flow_layout(&widths, available_width, button_height, BUTTON_SPACING);
+
+    // Overlay size (width is always full), leaving room for three lines of info below the buttons
+    let padding = 5.0;
+    let info_height = 3.0 * (self.base_font_size + padding);
+    let overlay_height = min_overlay_height.max(OVERLAY_PADDING * 2.0 + buttons_height + info_height);
+    let overlay_top = self.height as f64 - overlay_height;
+
     // Colors
     let bg_color = [0.0, 0.0, 0.0, self.ui_opacity as f32];
 
@@ -212,58 +418,79 @@ impl RendererBase for UI<'static> {
     // Draw the overlay
     rectangle(bg_color, overlay_rect, context.transform, gl);
 
-    let mut selected_device = String::from("No device selected");
-    // Check if we have a device selected
-    if !self.available_devices.is_empty() && self.selected_device < self.available_devices.len() {
-      selected_device = self.available_devices[self.selected_device].name.clone();
+    // Draw the buttons where the layout put them
+    let mut rects = Vec::new();
+    for (group, group_positions) in labels.into_iter().zip(positions.iter()) {
+      for (label, position) in group.into_iter().zip(group_positions.iter()) {
+        let begin_point = [OVERLAY_PADDING + position[0], overlay_top + OVERLAY_PADDING + position[1]];
+        rects.push(self.draw_text_button(begin_point, label, gl, context));
+      }
     }
 
-    self.device_info = format!("IN: {}", selected_device);
-
-    // Draw the input selection button
-    self.input_selector_button_rect = self.draw_text_button([10.0, overlay_rect[1] + 10.0], self.device_info.clone(), gl, context);
-
-    // ... and the renderer
-    self.renderer_selector_button_rect = self.draw_text_button(
-      [10.0 + self.input_selector_button_rect[2] + 20.0, overlay_rect[1] + 10.0],
-      format!("Renderer: {}", self.available_renderers[self.selected_renderer].clone()),
-      gl, context);
+    self.host_selector_button_rect = rects[0];
+    self.input_selector_button_rect = rects[1];
+    self.sample_rate_selector_button_rect = rects[2];
+    self.channels_selector_button_rect = rects[3];
+    self.device_buffer_selector_button_rect = rects[4];
+    self.window_selector_button_rect = rects[5];
+    self.fft_size_selector_button_rect = rects[6];
+    self.overlap_selector_button_rect = rects[7];
+    self.padding_button_rect = rects[8];
+    self.smoothing_selector_button_rect = rects[9];
+    self.tuning_selector_button_rect = rects[10];
+    self.renderer_selector_button_rect = rects[11];
+    self.view_selector_button_rect = rects[12];
+    self.bands_selector_button_rect = rects[13];
+    self.decibel_range_selector_button_rect = rects[14];
+    self.routing_selector_button_rect = rects[15];
 
     let fg_color = [1.0, 1.0, 1.0, self.ui_opacity as f32];
 
     // Draw a small spectrogram to indicate whether audio is actually being received
-    let amp_bar_height = self.renderer_selector_button_rect[3] as f32;
-    let start_x = self.renderer_selector_button_rect[0] + self.renderer_selector_button_rect[2] + 10.0;
-    let start_y = self.renderer_selector_button_rect[1] + self.renderer_selector_button_rect[3];
-    let w = 50.0 / audio.amplitude[0].len() as f64;
-    for (i, sample) in audio.amplitude[0].iter().enumerate() {
-      let h = (sample.abs() * amp_bar_height) as f64;
+    let level_position = positions.last().and_then(|group| group.last()).copied().unwrap_or_default();
+    let amp_bar_height = button_height as f32;
+    let start_x = OVERLAY_PADDING + level_position[0];
+    let start_y = overlay_top + OVERLAY_PADDING + level_position[1] + button_height;
+    let [amplitude, _] = audio.routed(&audio.amplitude);
+    let w = LEVEL_DISPLAY_WIDTH / amplitude.len() as f64;
+    for (i, sample) in amplitude.iter().enumerate() {
+      let h = (audio.decibel_scale.normalize(*sample) * amp_bar_height) as f64;
       rectangle(fg_color, [start_x + i as f64 * w, start_y - h, w, h], context.transform, gl);
     }
 
     // Now provide audio information in the next lines
-    let padding = 5.0;
+    let text_top = overlay_top + OVERLAY_PADDING + buttons_height;
 
     // Sample rate
     text::Text::new_color(fg_color, self.base_font_size as u32).draw(
-      format!("Sample rate: {} Hz", format_number(audio.sample_rate as f64)).as_str(),
+      format!("Sample rate: {} Hz, tempo: {}", format_number(audio.sample_rate as f64), if audio.bpm > 0.0 {
+        format!("{} BPM", audio.bpm.round())
+      } else {
+        String::from("unknown")
+      }).as_str(),
       &mut self.ui_font,
       &context.draw_state,
-      context.transform.trans(10.0 + padding, overlay_rect[1] + 10.0 + self.base_font_size * 2.0 + 3.0 * padding),
+      context.transform.trans(10.0 + padding, text_top + self.base_font_size * 1.0 + 1.0 * padding),
       gl
     ).unwrap();
 
     // Buffer size
     text::Text::new_color(fg_color, self.base_font_size as u32).draw(
-      format!("Buffer size: {} samples", format_number(audio.buffer_size as f64)).as_str(),
+      format!(
+        "Buffer size: {} samples (FFT length: {}, next frame every {} samples)",
+        format_number(audio.buffer_size as f64),
+        format_number(audio.transform_size as f64),
+        format_number(audio.hop_size as f64)
+      ).as_str(),
       &mut self.ui_font,
       &context.draw_state,
-      context.transform.trans(10.0 + padding, overlay_rect[1] + 10.0 + self.base_font_size * 3.0 + 4.0 * padding),
+      context.transform.trans(10.0 + padding, text_top + self.base_font_size * 2.0 + 2.0 * padding),
       gl
     ).unwrap();
 
+    let [frequency, _] = audio.routed(&audio.frequency);
     let mut max_frequency = 0.0;
-    for sample in audio.frequency[0].clone() {
+    for sample in frequency.iter().copied() {
       if sample > max_frequency {
         max_frequency = sample;
       }
@@ -281,12 +508,12 @@ impl RendererBase for UI<'static> {
       format!(
         "Analyzed frequencies: {} Hz to {} Hz (channels: {})",
         format_number(audio.bin_frequency.round() as f64),
-        format_number((audio.bin_frequency * audio.frequency[0].len() as f32).round() as f64),
+        format_number((audio.bin_frequency * frequency.len() as f32).round() as f64),
         audio.channels
       ).as_str(),
       &mut self.ui_font,
       &context.draw_state,
-      context.transform.trans(10.0 + padding, overlay_rect[1] + 10.0 + self.base_font_size * 4.0 + 5.0 * padding),
+      context.transform.trans(10.0 + padding, text_top + self.base_font_size * 3.0 + 3.0 * padding),
       gl
     ).unwrap();
 
@@ -301,7 +528,7 @@ impl RendererBase for UI<'static> {
     }
   }
 
-  fn update (&mut self, _args: &UpdateArgs) {
+  fn update (&mut self, args: &UpdateArgs) {
     let now = time::Instant::now();
     if now.duration_since(self.mouse_last_moved) > time::Duration::new(self.menu_display_time, 0) {
       self.should_display_ui = false;
@@ -313,6 +540,10 @@ impl RendererBase for UI<'static> {
     } else if self.should_display_ui && self.ui_opacity < self.target_opacity {
       self.ui_opacity += 0.1;
     }
+
+    for elem in self.ui_elements.iter_mut() {
+      elem.update(args);
+    }
   }
 
   fn on_cursor_movement (&mut self, x: f64, y: f64) {
@@ -332,18 +563,40 @@ impl RendererBase for UI<'static> {
     if !is_over_window {
       self.should_display_ui = false;
     }
+
+    for elem in self.ui_elements.iter_mut() {
+      elem.on_cursor_state(is_over_window);
+    }
   }
 
   fn on_click (&mut self) {
     // Check for generated events on the UI Elements
     // Now propagate to all UI elements
+    let mut selected = false;
     for elem in self.ui_elements.iter_mut() {
-      if let Some(event) = elem.on_click() {
-        if let UIEvent::Selection(idx, id) = event {
+      if let Some(UIEvent::Selection(idx, id)) = elem.on_click() {
+        selected = true;
+        if let Some(sender) = self.event_sender.as_ref() {
           // Send event to application
-          if self.event_sender.is_some() && id == AUDIO_IO_ID {
-            self.event_sender.as_ref().unwrap().send(UIEvent::RequestChangeAudioDevice(idx)).unwrap();
-          } else if self.event_sender.is_some() && id == RENDERER_ID {
+          if id == HOST_ID {
+            sender.send(UIEvent::RequestChangeHost(idx)).unwrap();
+          } else if id == AUDIO_IO_ID {
+            sender.send(UIEvent::RequestChangeAudioDevice(idx)).unwrap();
+          } else if id == SAMPLE_RATE_ID || id == CHANNELS_ID || id == DEVICE_BUFFER_ID {
+            // The items carry the values themselves, and the device fills in the rest
+            let current = StreamConfig {
+              channels: self.channels as u16,
+              sample_rate: SampleRate(self.sample_rate),
+              buffer_size: self.device_buffer_size.clone()
+            };
+            let requested = requested_stream_config(id, idx, current);
+
+            let device = self.available_devices.get(self.selected_device);
+            if let Some(config) = device.and_then(|device| device.closest_config(requested.channels, requested.sample_rate.0, requested.buffer_size)) {
+              self.device_buffer_size = config.buffer_size.clone();
+              sender.send(UIEvent::RequestChangeStreamConfig(config)).unwrap();
+            }
+          } else if id == RENDERER_ID {
             // let event = match idx {
             //   1 => {
             //     RendererType::Circle
@@ -354,70 +607,199 @@ impl RendererBase for UI<'static> {
             //   _ => { RendererType::Square } // Everything 0 and non-covered
             // };
 
-            self.event_sender.as_ref().unwrap().send(UIEvent::RequestChangeRenderer(idx)).unwrap();
+            sender.send(UIEvent::RequestChangeRenderer(idx)).unwrap();
+          } else if id == WINDOW_ID {
+            sender.send(UIEvent::RequestChangeWindow(idx)).unwrap();
+          } else if id == FFT_SIZE_ID {
+            sender.send(UIEvent::RequestChangeFftSize(idx)).unwrap();
+          } else if id == OVERLAP_ID {
+            sender.send(UIEvent::RequestChangeOverlap(idx)).unwrap();
+          } else if id == BANDS_ID {
+            sender.send(UIEvent::RequestChangeBandLayout(idx)).unwrap();
+          } else if id == DECIBEL_RANGE_ID {
+            sender.send(UIEvent::RequestChangeDecibelRange(idx)).unwrap();
+          } else if id == SMOOTHING_ID {
+            sender.send(UIEvent::RequestChangeSmoothing(idx)).unwrap();
+          } else if id == TUNING_ID {
+            sender.send(UIEvent::RequestChangeReferenceTuning(idx)).unwrap();
+          } else if id == VIEW_ID {
+            sender.send(UIEvent::RequestChangeView(idx)).unwrap();
+          } else if id == ROUTING_ID {
+            sender.send(UIEvent::RequestChangeChannelRouting(idx)).unwrap();
           }
         }
       }
     }
 
-    // Display the dropdown if the cursor is currently in the input device selector button rect
-    if cursor_in_rect(
-      [self.last_cursor_x, self.last_cursor_y],
-      self.input_selector_button_rect
-    ) && self.input_selector_index < 0 {
-        let mut items = Vec::new();
-        for device in self.available_devices.iter() {
-          items.push((device.index, device.name.clone()));
-        }
+    // Dropdowns can cover the buttons in the rows above them, so a click on an
+    // item must not reach the button underneath. It only closes the dropdown.
+    if selected {
+      self.open_dropdown = None;
+      self.ui_elements.clear();
+      return;
+    }
 
-        self.ui_elements.push(
-          Box::new(
-            UIDropdown::create(
-              AUDIO_IO_ID,
-              items, true,
-              [self.input_selector_button_rect[0], self.input_selector_button_rect[1]],
-              self.input_selector_button_rect[2],
-              self.base_font_size,
-              self.font_path.clone()
-            )
-          )
-        );
-        // Save the index for later
-        self.input_selector_index = self.ui_elements.len() as i32 - 1;
-    } else if self.input_selector_index > -1 && !self.ui_elements.is_empty() {
-        // Remove that thing again
-        self.ui_elements.remove(self.input_selector_index as usize);
-        self.input_selector_index = -1;
+    if cursor_in_rect([self.last_cursor_x, self.last_cursor_y], self.padding_button_rect) {
+      self.zero_padding = !self.zero_padding;
+      if let Some(sender) = self.event_sender.as_ref() {
+        sender.send(UIEvent::RequestChangeZeroPadding(self.zero_padding)).unwrap();
       }
+    }
+
+    // Any click closes the open dropdown ...
+    let was_open = self.open_dropdown.take();
+    self.ui_elements.clear();
 
-    if cursor_in_rect([self.last_cursor_x, self.last_cursor_y],
-    self.renderer_selector_button_rect) && self.renderer_selector_index < 0 {
+    // ... and a click on one of the buttons opens its dropdown (unless it's the one we just closed)
+    let cursor = [self.last_cursor_x, self.last_cursor_y];
+    if cursor_in_rect(cursor, self.host_selector_button_rect) && was_open != Some(HOST_ID) {
+      let mut items = Vec::new();
+      for (idx, host) in self.available_hosts.iter().enumerate() {
+        items.push((idx, String::from(host.name())));
+      }
+      self.open_dropdown(HOST_ID, items, self.host_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.input_selector_button_rect) && was_open != Some(AUDIO_IO_ID) {
       let mut items = Vec::new();
-        for (i, renderer) in self.available_renderers.iter().enumerate() {
-          items.push((i, renderer.clone()));
+      for device in self.available_devices.iter() {
+        items.push((device.index, device.name.clone()));
+      }
+      self.open_dropdown(AUDIO_IO_ID, items, self.input_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.sample_rate_selector_button_rect) && was_open != Some(SAMPLE_RATE_ID) {
+      let mut items = Vec::new();
+      if let Some(device) = self.available_devices.get(self.selected_device) {
+        for sample_rate in device.sample_rates(self.channels as u16) {
+          items.push((sample_rate as usize, format!("{} Hz", format_number(sample_rate as f64))));
         }
-
-        self.ui_elements.push(
-          Box::new(
-            UIDropdown::create(
-              RENDERER_ID,
-              items, true,
-              [self.renderer_selector_button_rect[0], self.renderer_selector_button_rect[1]],
-              self.renderer_selector_button_rect[2],
-              self.base_font_size,
-              self.font_path.clone()
-            )
-          )
-        );
-        // Save the index for later
-        self.input_selector_index = self.ui_elements.len() as i32 - 1;
-      } else if self.renderer_selector_index > -1 && !self.ui_elements.is_empty() {
-        self.ui_elements.remove(self.renderer_selector_index as usize);
-        self.renderer_selector_index = -1;
       }
+      if !items.is_empty() {
+        self.open_dropdown(SAMPLE_RATE_ID, items, self.sample_rate_selector_button_rect);
+      }
+    } else if cursor_in_rect(cursor, self.channels_selector_button_rect) && was_open != Some(CHANNELS_ID) {
+      let mut items = Vec::new();
+      if let Some(device) = self.available_devices.get(self.selected_device) {
+        for channels in device.channel_counts() {
+          items.push((channels as usize, format!("{}", channels)));
+        }
+      }
+      if !items.is_empty() {
+        self.open_dropdown(CHANNELS_ID, items, self.channels_selector_button_rect);
+      }
+    } else if cursor_in_rect(cursor, self.device_buffer_selector_button_rect) && was_open != Some(DEVICE_BUFFER_ID) {
+      let mut items = Vec::new();
+      if let Some(device) = self.available_devices.get(self.selected_device) {
+        for buffer_size in device.buffer_sizes(self.channels as u16, self.sample_rate) {
+          let value = match buffer_size { BufferSize::Fixed(frames) => frames as usize, BufferSize::Default => 0 };
+          items.push((value, format_buffer_size(&buffer_size)));
+        }
+      }
+      // Devices without configurations (such as files) only have the default
+      if items.len() > 1 {
+        self.open_dropdown(DEVICE_BUFFER_ID, items, self.device_buffer_selector_button_rect);
+      }
+    } else if cursor_in_rect(cursor, self.renderer_selector_button_rect) && was_open != Some(RENDERER_ID) {
+      let mut items = Vec::new();
+      for (i, renderer) in self.available_renderers.iter().enumerate() {
+        items.push((i, renderer.clone()));
+      }
+      self.open_dropdown(RENDERER_ID, items, self.renderer_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.window_selector_button_rect) && was_open != Some(WINDOW_ID) {
+      let mut items = Vec::new();
+      for (i, window) in WindowFunction::all().iter().enumerate() {
+        items.push((i, window.name()));
+      }
+      self.open_dropdown(WINDOW_ID, items, self.window_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.fft_size_selector_button_rect) && was_open != Some(FFT_SIZE_ID) {
+      let mut items = Vec::new();
+      for (i, size) in FFT_SIZES.iter().enumerate() {
+        items.push((i, format_number(*size as f64)));
+      }
+      self.open_dropdown(FFT_SIZE_ID, items, self.fft_size_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.overlap_selector_button_rect) && was_open != Some(OVERLAP_ID) {
+      let mut items = Vec::new();
+      for (i, overlap) in OVERLAPS.iter().enumerate() {
+        items.push((i, format!("{} %", overlap * 100.0)));
+      }
+      self.open_dropdown(OVERLAP_ID, items, self.overlap_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.bands_selector_button_rect) && was_open != Some(BANDS_ID) {
+      let mut items = Vec::new();
+      for (i, layout) in BandLayout::all().iter().enumerate() {
+        items.push((i, layout.name()));
+      }
+      self.open_dropdown(BANDS_ID, items, self.bands_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.decibel_range_selector_button_rect) && was_open != Some(DECIBEL_RANGE_ID) {
+      let mut items = Vec::new();
+      for (i, (floor, ceiling)) in DECIBEL_RANGES.iter().enumerate() {
+        items.push((i, DecibelScale::create(*floor, *ceiling).name()));
+      }
+      self.open_dropdown(DECIBEL_RANGE_ID, items, self.decibel_range_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.smoothing_selector_button_rect) && was_open != Some(SMOOTHING_ID) {
+      let mut items = Vec::new();
+      for (i, ballistics) in Ballistics::all().iter().enumerate() {
+        items.push((i, ballistics.name()));
+      }
+      self.open_dropdown(SMOOTHING_ID, items, self.smoothing_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.tuning_selector_button_rect) && was_open != Some(TUNING_ID) {
+      let mut items = Vec::new();
+      for (i, reference_tuning) in REFERENCE_TUNINGS.iter().enumerate() {
+        items.push((i, format!("{} Hz", reference_tuning)));
+      }
+      self.open_dropdown(TUNING_ID, items, self.tuning_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.view_selector_button_rect) && was_open != Some(VIEW_ID) {
+      let mut items = Vec::new();
+      for (i, view) in SpectrumView::all().iter().enumerate() {
+        items.push((i, view.name()));
+      }
+      self.open_dropdown(VIEW_ID, items, self.view_selector_button_rect);
+    } else if cursor_in_rect(cursor, self.routing_selector_button_rect) && was_open != Some(ROUTING_ID) {
+      let mut items = Vec::new();
+      for (i, routing) in ChannelRouting::all(self.channels).iter().enumerate() {
+        items.push((i, routing.name()));
+      }
+      self.open_dropdown(ROUTING_ID, items, self.routing_selector_button_rect);
     }
+  }
+
+  fn on_keypress (&mut self, key: Key) {
+    for elem in self.ui_elements.iter_mut() {
+      elem.on_keypress(key);
+    }
+  }
+}
+
+/// Applies the value picked from the sample rate, channels or device buffer
+/// dropdown to the current stream configuration. The device buffer dropdown
+/// offers 0 for the default buffer size.
+fn requested_stream_config (id: usize, value: usize, current: StreamConfig) -> StreamConfig {
+  let mut config = current;
+  if id == SAMPLE_RATE_ID {
+    config.sample_rate = SampleRate(value as u32);
+  } else if id == CHANNELS_ID {
+    config.channels = value as u16;
+  } else if id == DEVICE_BUFFER_ID {
+    config.buffer_size = if value == 0 { BufferSize::Default } else { BufferSize::Fixed(value as u32) };
+  }
+  config
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn current () -> StreamConfig {
+    StreamConfig { channels: 2, sample_rate: SampleRate(44_100), buffer_size: BufferSize::Default }
+  }
+
+  #[test]
+  fn maps_a_selection_to_the_stream_config () {
+    let config = requested_stream_config(SAMPLE_RATE_ID, 48_000, current());
+    assert_eq!((config.channels, config.sample_rate.0, config.buffer_size), (2, 48_000, BufferSize::Default));
+
+    let config = requested_stream_config(CHANNELS_ID, 1, current());
+    assert_eq!((config.channels, config.sample_rate.0, config.buffer_size), (1, 44_100, BufferSize::Default));
 
-  fn on_keypress (&mut self, _key: Key) {
-    // ...
+    let config = requested_stream_config(DEVICE_BUFFER_ID, 256, current());
+    assert_eq!(config.buffer_size, BufferSize::Fixed(256));
+    let config = requested_stream_config(DEVICE_BUFFER_ID, 0, config);
+    assert_eq!(config.buffer_size, BufferSize::Default);
   }
 }
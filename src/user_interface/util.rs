@@ -3,11 +3,41 @@
 use std::env::current_exe;
 use std::path::Path;
 use std::fs;
+use cpal::BufferSize;
 
 pub fn cursor_in_rect (point: [f64; 2], rect: [f64; 4]) -> bool {
   point[0] > rect[0] && point[0] < rect[0] + rect[2] && point[1] > rect[1] && point[1] < rect[1] + rect[3]
 }
 
+/// Lays out rows of items of the given widths from left to right. Each group
+/// starts a new row and wraps onto further rows where it doesn't fit into the
+/// available width. Returns the top left corner of each item, relative to the
+/// layout, and the height of all rows together.
+pub fn flow_layout (groups: &[Vec<f64>], available_width: f64, row_height: f64, spacing: f64) -> (Vec<Vec<[f64; 2]>>, f64) {
+  let mut positions = Vec::with_capacity(groups.len());
+  let mut y = 0.0;
+  for (idx, group) in groups.iter().enumerate() {
+    if idx > 0 {
+      y += row_height + spacing / 2.0;
+    }
+
+    let mut x = 0.0;
+    let mut row = Vec::with_capacity(group.len());
+    for width in group.iter() {
+      // The first item of a row stays there, even if it is too wide
+      if x > 0.0 && x + width > available_width {
+        x = 0.0;
+        y += row_height + spacing / 2.0;
+      }
+      row.push([x, y]);
+      x += width + spacing;
+    }
+    positions.push(row);
+  }
+
+  (positions, y + row_height)
+}
+
 pub fn format_number (number: f64) -> String {
   // Number: 1_465.435
   let mut as_chars: Vec<char> = Vec::new();
@@ -53,11 +83,16 @@ pub fn format_number (number: f64) -> String {
   }
 }
 
+/// Formats the buffer size of an input device, e.g. 512 frames
+pub fn format_buffer_size (buffer_size: &BufferSize) -> String {
+  match buffer_size {
+    BufferSize::Default => String::from("default"),
+    BufferSize::Fixed(frames) => format!("{} frames", format_number(*frames as f64))
+  }
+}
+
 /// Finds the font for the user interface
 pub fn find_font () -> Result<String, String> {
-  use std::io::Write;
-  let mut file = std::fs::File::create("/Users/hendrik/Desktop/log.txt").unwrap();
-  // file.write_all(b"Could not find the font path!").unwrap();
   // This function basically only finds the necessary font.
   let __filename = current_exe().unwrap();
   let __dirname = __filename.parent().unwrap();
@@ -70,7 +105,6 @@ pub fn find_font () -> Result<String, String> {
 
   for path in possible_locations.iter() {
     let font_path = String::from(Path::new(&__dirname).join(path).to_str().unwrap());
-    file.write_all(format!("\nLooking at path: {} (Dirname: {:? })", font_path, &__dirname).as_bytes()).unwrap();
     let metadata = fs::metadata(&font_path);
     if let Ok(m) = metadata {
       if m.is_file() {